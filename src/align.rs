@@ -0,0 +1,101 @@
+use crate::line::{Line, Style};
+use crate::wrap::width;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+impl Alignment {
+    /// Parses a CSS `text-align` or HTML `align` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "left" | "start" => Some(Alignment::Left),
+            "right" | "end" => Some(Alignment::Right),
+            "center" => Some(Alignment::Center),
+            "justify" => Some(Alignment::Justify),
+            _ => None,
+        }
+    }
+}
+
+/// Positions a wrapped line inside `max_width` cells. The last line of a justified
+/// paragraph stays left aligned.
+pub fn align(line: Line, alignment: Alignment, max_width: usize, last_in_paragraph: bool) -> Line {
+    let free = max_width.saturating_sub(width(&line.text()));
+    if free == 0 {
+        return line;
+    }
+    match alignment {
+        Alignment::Left => line,
+        Alignment::Right => pad(line, free),
+        Alignment::Center => pad(line, free / 2),
+        Alignment::Justify if last_in_paragraph => line,
+        Alignment::Justify => justify(line, free),
+    }
+}
+
+fn pad(line: Line, cells: usize) -> Line {
+    let mut padded = Line::new();
+    padded.push(&" ".repeat(cells), &Style::default());
+    padded.spans.extend(line.spans);
+    padded
+}
+
+/// Spreads `free` cells over the gaps between words, earlier gaps getting the remainder.
+fn justify(line: Line, free: usize) -> Line {
+    let gaps: usize = line
+        .spans
+        .iter()
+        .map(|span| span.text.matches(' ').count())
+        .sum();
+    if gaps == 0 {
+        return line;
+    }
+
+    let mut justified = Line::new();
+    let mut gap = 0;
+    for span in &line.spans {
+        let mut text = String::with_capacity(span.text.len() + free);
+        for c in span.text.chars() {
+            text.push(c);
+            if c == ' ' {
+                let extra = free / gaps + usize::from(gap < free % gaps);
+                text.extend(std::iter::repeat_n(' ', extra));
+                gap += 1;
+            }
+        }
+        justified.push(&text, &span.style);
+    }
+    justified
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::align::{align, Alignment};
+    use crate::line::Line;
+
+    #[test]
+    fn justifies_all_but_last_line() {
+        let line = align(Line::from("a b c"), Alignment::Justify, 8, false);
+        assert_eq!(line.text(), "a   b  c");
+
+        let last = align(Line::from("a b c"), Alignment::Justify, 8, true);
+        assert_eq!(last.text(), "a b c");
+    }
+
+    #[test]
+    fn centers_and_right_aligns() {
+        assert_eq!(
+            align(Line::from("ab"), Alignment::Center, 7, true).text(),
+            "  ab"
+        );
+        assert_eq!(
+            align(Line::from("ab"), Alignment::Right, 5, true).text(),
+            "   ab"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::line::{Line, Style};
+use unicode_bidi::{get_base_direction, Level, ParagraphBidiInfo};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Direction text of a paragraph is read in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    /// Value of a `dir` attribute, `auto` taking the direction of the first strong
+    /// character of `text`.
+    pub fn parse(value: &str, text: impl FnOnce() -> String) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ltr" => Some(Direction::Ltr),
+            "rtl" => Some(Direction::Rtl),
+            "auto" => match get_base_direction(text().as_str()) {
+                unicode_bidi::Direction::Rtl => Some(Direction::Rtl),
+                _ => Some(Direction::Ltr),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Line in the order it's drawn in, as the Unicode Bidirectional Algorithm puts it for a
+/// paragraph going in `direction`. Right-to-left runs are reversed by grapheme with
+/// brackets mirrored, and styles stay with their text.
+pub fn reorder(line: &Line, direction: Direction) -> Line {
+    let text = line.text();
+    let level = match direction {
+        Direction::Ltr => Level::ltr(),
+        Direction::Rtl => Level::rtl(),
+    };
+    let bidi = ParagraphBidiInfo::new(&text, Some(level));
+    if !bidi.has_rtl() {
+        return line.clone();
+    }
+
+    // Style of every byte of the text
+    let mut styles: Vec<&Style> = Vec::with_capacity(text.len());
+    for span in &line.spans {
+        styles.extend(std::iter::repeat_n(&span.style, span.text.len()));
+    }
+
+    let (levels, runs) = bidi.visual_runs(0..text.len());
+    let mut reordered = Line {
+        image: line.image.clone(),
+        ..Line::new()
+    };
+    for run in runs {
+        let graphemes: Vec<(usize, &str)> = text[run.clone()]
+            .grapheme_indices(true)
+            .map(|(offset, grapheme)| (run.start + offset, grapheme))
+            .collect();
+        if levels[run.start].is_rtl() {
+            for (offset, grapheme) in graphemes.into_iter().rev() {
+                reordered.push(&mirror(grapheme), styles[offset]);
+            }
+        } else {
+            for (offset, grapheme) in graphemes {
+                reordered.push(grapheme, styles[offset]);
+            }
+        }
+    }
+    reordered
+}
+
+/// Bracket facing the other way, as it's drawn in right-to-left text.
+fn mirror(grapheme: &str) -> String {
+    let mirrored = match grapheme {
+        "(" => ")",
+        ")" => "(",
+        "[" => "]",
+        "]" => "[",
+        "{" => "}",
+        "}" => "{",
+        "<" => ">",
+        ">" => "<",
+        "«" => "»",
+        "»" => "«",
+        "‹" => "›",
+        "›" => "‹",
+        _ => grapheme,
+    };
+    mirrored.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bidi::{reorder, Direction};
+    use crate::line::{Line, Style};
+
+    #[test]
+    fn right_to_left_runs_are_reversed() {
+        let mut line = Line::new();
+        line.push("He said ", &Style::default());
+        line.push("שלום (עולם)", &Style::new("i"));
+        line.push(".", &Style::default());
+
+        let reordered = reorder(&line, Direction::Ltr);
+        assert_eq!(reordered.text(), "He said (םלוע) םולש.");
+        assert_eq!(reordered.spans[1].text, "(םלוע) םולש");
+        assert_eq!(reordered.spans[1].style, Style::new("i"));
+
+        let reordered = reorder(&Line::from("שלום, world"), Direction::Rtl);
+        assert_eq!(reordered.text(), "world ,םולש");
+        assert_eq!(
+            Direction::parse("auto", || "  שלום".to_owned()),
+            Some(Direction::Rtl)
+        );
+    }
+}
@@ -1,3 +1,10 @@
+use crate::align::Alignment;
+use crate::graphics::Graphics;
+use crate::hyphen::Hyphenation;
+use crate::layout::Overflow;
+use crate::math::MathLayout;
+use crate::ruby::RubyLayout;
+
 pub struct InputKeys {
     pub select: char,
     pub up: char,
@@ -5,6 +12,22 @@ pub struct InputKeys {
     pub back: char,
     pub left: char,
     pub right: char,
+    pub note: char,
+    /// Moves the focus to the next link on the screen, `select` follows it.
+    pub link: char,
+    pub history_back: char,
+    pub history_forward: char,
+    /// Labels every link on the screen, see `hint_alphabet`.
+    pub hints: char,
+    /// Shows the image nearest the top of the screen over the whole screen, where
+    /// movement keys pan it.
+    pub image: char,
+    pub zoom_in: char,
+    pub zoom_out: char,
+    /// Hands the shown image to `image_viewer`.
+    pub open_image: char,
+    /// Switches to the next theme.
+    pub theme: char,
 }
 
 pub struct ReaderConfig<'a> {
@@ -12,6 +35,40 @@ pub struct ReaderConfig<'a> {
     pub margin_y: u16,
     pub tmp_path: &'a str,
     pub keys: &'a InputKeys,
+    pub hyphenation: Hyphenation,
+    pub alignment: Alignment,
+    pub heading_alignment: Alignment,
+    pub pre_overflow: Overflow,
+    pub paragraph_spacing: usize,
+    pub first_line_indent: usize,
+    pub scene_break: &'a str,
+    pub book_colors: bool,
+    /// Clicks open notes, but the terminal's own text selection stops working.
+    pub mouse: bool,
+    /// Letters link hints are made of, escape or `back` cancel them so it shouldn't
+    /// contain the `back` key.
+    pub hint_alphabet: &'a str,
+    /// Command external links are opened with, the URL is its last argument.
+    pub opener: &'a str,
+    /// Whether external links are sent to the terminal as OSC 8 hyperlinks.
+    pub hyperlinks: bool,
+    /// Whether images are drawn, how depends on what the terminal supports.
+    pub images: bool,
+    /// Way of drawing images, asked from the terminal when not set.
+    pub graphics: Option<Graphics>,
+    /// Command images are opened with, the path is its last argument.
+    pub image_viewer: &'a str,
+    pub math: MathLayout,
+    /// Whether lines are reordered for right-to-left text, turn it off in terminals
+    /// which do it themselves.
+    pub bidi: bool,
+    pub ruby: RubyLayout,
+    /// Name of the theme used at start, `dark`, `light`, `sepia`, `high-contrast` or one
+    /// of `theme_file`, set with `--theme`.
+    pub theme: &'a str,
+    /// File with more themes, see `Theme`. Set with `--theme-file`, otherwise
+    /// `papcio/themes` in the user's config directory is read when it's there.
+    pub theme_file: Option<&'a str>,
 }
 
 impl<'a> ReaderConfig<'a> {
@@ -27,11 +84,41 @@ impl<'a> ReaderConfig<'a> {
                 back: 'q',
                 left: 'a',
                 right: 'd',
+                note: 'n',
+                link: '\t',
+                history_back: 'b',
+                history_forward: 'B',
+                hints: 'f',
+                image: 'v',
+                zoom_in: '+',
+                zoom_out: '-',
+                open_image: 'o',
+                theme: 't',
             },
+            hyphenation: Hyphenation::new(),
+            alignment: Alignment::Justify,
+            heading_alignment: Alignment::Center,
+            pre_overflow: Overflow::Wrap,
+            paragraph_spacing: 1,
+            first_line_indent: 0,
+            scene_break: "* * *",
+            book_colors: false,
+            mouse: true,
+            hint_alphabet: "sadfjklewcmpgh",
+            opener: "xdg-open",
+            hyperlinks: true,
+            images: true,
+            graphics: None,
+            image_viewer: "xdg-open",
+            math: MathLayout::Linear,
+            bidi: true,
+            ruby: RubyLayout::Above,
+            theme: "dark",
+            theme_file: None,
         }
     }
 
-    pub fn load_from_file(config_file_path: &str) -> Self {
+    pub fn load_from_file(_config_file_path: &str) -> Self {
         todo!()
     }
 }
@@ -0,0 +1,126 @@
+use crate::dom::{Document, NodeId};
+use crate::html::resolve_link;
+
+/// What the splash screen shows before the table of contents.
+#[derive(Debug, Default, PartialEq)]
+pub struct Cover {
+    /// Path of the cover image.
+    pub image: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl Cover {
+    /// Reads the cover of the package document at `opf_path`. The image is the item with
+    /// the `cover-image` property, the one EPUB 2's `<meta name="cover">` points to, or the
+    /// first image of the first spine item.
+    pub fn from_opf(opf: &str, opf_path: &str) -> Self {
+        let doc = Document::parse(opf);
+        let elements = |name: &str| {
+            doc.descendants(doc.root())
+                .into_iter()
+                .filter(|id| doc.name(*id) == Some(name))
+                .collect::<Vec<_>>()
+        };
+        let text = |name: &str| {
+            elements(name)
+                .first()
+                .map(|id| doc.text_content(*id).trim().to_owned())
+                .filter(|text| !text.is_empty())
+        };
+
+        let items = elements("item");
+        let item = |id: &str| {
+            items
+                .iter()
+                .copied()
+                .find(|item| doc.attr(*item, "id") == Some(id))
+        };
+        let cover_image = items.iter().copied().find(|item| {
+            doc.attr(*item, "properties")
+                .is_some_and(|properties| properties.split_whitespace().any(|p| p == "cover-image"))
+        });
+        let cover_meta = elements("meta")
+            .into_iter()
+            .find(|meta| doc.attr(*meta, "name") == Some("cover"))
+            .and_then(|meta| item(doc.attr(meta, "content")?));
+        let first_page = elements("itemref")
+            .first()
+            .and_then(|itemref| item(doc.attr(*itemref, "idref")?));
+
+        let image = [cover_image, cover_meta, first_page]
+            .into_iter()
+            .flatten()
+            .find_map(|item| image_of(&doc, item, opf_path));
+        Self {
+            image,
+            title: text("dc:title"),
+            author: text("dc:creator"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.image.is_none() && self.title.is_none()
+    }
+}
+
+/// Path of a manifest item's image, itself or the first one of a page.
+fn image_of(doc: &Document, item: NodeId, opf_path: &str) -> Option<String> {
+    let (path, _) = resolve_link(opf_path, doc.attr(item, "href")?);
+    let media_type = doc.attr(item, "media-type").unwrap_or("");
+    if media_type.starts_with("image/") {
+        return Some(path);
+    }
+
+    let page = Document::from_file(&path).ok()?;
+    let image =
+        page.descendants(page.root())
+            .into_iter()
+            .find_map(|id| match page.name(id)? {
+                "img" => page.attr(id, "src"),
+                "image" => page
+                    .attr(id, "xlink:href")
+                    .or_else(|| page.attr(id, "href")),
+                _ => None,
+            })?;
+    Some(resolve_link(&path, image).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cover::Cover;
+
+    #[test]
+    fn cover_comes_from_the_manifest_or_the_first_page() {
+        let epub3 = r#"<package><metadata>
+            <dc:title>Pan Tadeusz</dc:title><dc:creator>Adam Mickiewicz</dc:creator>
+            </metadata><manifest>
+            <item id="c" href="images/cover.jpg" media-type="image/jpeg" properties="cover-image"/>
+            </manifest></package>"#;
+        assert_eq!(
+            Cover::from_opf(epub3, "book/OEBPS/content.opf"),
+            Cover {
+                image: Some("book/OEBPS/images/cover.jpg".to_owned()),
+                title: Some("Pan Tadeusz".to_owned()),
+                author: Some("Adam Mickiewicz".to_owned()),
+            }
+        );
+
+        let epub2 = r#"<package><metadata><meta name="cover" content="img"/></metadata>
+            <manifest><item id="img" href="cover.png" media-type="image/png"/></manifest>
+            </package>"#;
+        assert_eq!(
+            Cover::from_opf(epub2, "content.opf").image,
+            Some("cover.png".to_owned())
+        );
+
+        let spine = r#"<package><manifest>
+            <item id="text" href="links_file.html" media-type="application/xhtml+xml"/>
+            <item id="page" href="cover_file.html" media-type="application/xhtml+xml"/>
+            </manifest><spine><itemref idref="page"/><itemref idref="text"/></spine></package>"#;
+        assert_eq!(
+            Cover::from_opf(spine, "test_data/content.opf").image,
+            Some("test_data/images/cover.jpg".to_owned())
+        );
+    }
+}
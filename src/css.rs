@@ -0,0 +1,331 @@
+use crate::align::Alignment;
+use crate::bidi::Direction;
+use crate::dom::{Document, NodeId};
+use simplecss::{AttributeOperator, DeclarationTokenizer, PseudoClass, StyleSheet};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// What the book's CSS says about one element, limited to what a terminal can show.
+#[derive(Debug, Default)]
+pub struct ElementStyle {
+    /// Style keys like `bold` or `color:#ff0000`, resolved by the styler.
+    pub keys: Vec<String>,
+    pub alignment: Option<Alignment>,
+    pub hidden: bool,
+    /// `text-indent` in cells.
+    pub text_indent: Option<usize>,
+    /// `margin-left` in cells.
+    pub margin_left: Option<usize>,
+    pub small_caps: Option<bool>,
+    pub page_break_before: bool,
+    pub direction: Option<Direction>,
+}
+
+/// Texts of the stylesheets linked from or embedded in an HTML file, in document order.
+pub fn stylesheets(doc: &Document, html_path: &Path) -> Vec<String> {
+    let directory = html_path.parent().unwrap_or(Path::new(""));
+    doc.descendants(doc.root())
+        .into_iter()
+        .filter_map(|id| match doc.name(id)? {
+            "style" => Some(doc.text_content(id)),
+            "link" => {
+                let rel = doc.attr(id, "rel")?;
+                if !rel
+                    .split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+                {
+                    return None;
+                }
+                let href = doc.attr(id, "href")?;
+                let href = href.split(['#', '?']).next().unwrap_or(href);
+                fs::read_to_string(directory.join(href)).ok()
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn parse<'s>(sheets: &'s [String]) -> StyleSheet<'s> {
+    let mut stylesheet = StyleSheet::new();
+    for sheet in sheets {
+        stylesheet.parse_more(sheet);
+    }
+    stylesheet
+}
+
+/// Cascades matching rules and the `style` attribute of `id`. Colours are only kept when
+/// `colors` is set, they're often picked for a white page.
+pub fn element_style(
+    stylesheet: &StyleSheet,
+    doc: &Document,
+    id: NodeId,
+    colors: bool,
+) -> ElementStyle {
+    let element = Element { doc, id };
+    let matching: Vec<_> = stylesheet
+        .rules
+        .iter()
+        .filter(|rule| rule.selector.matches(&element))
+        .flat_map(|rule| {
+            rule.declarations
+                .iter()
+                .map(|d| (d.name, d.value, d.important))
+        })
+        .collect();
+    let inline: Vec<_> = doc
+        .attr(id, "style")
+        .map(|style| {
+            DeclarationTokenizer::from(style)
+                .map(|d| (d.name, d.value, d.important))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Rules are sorted by specificity, so later ones win, and important ones win over all
+    let mut declarations = HashMap::new();
+    for important in [false, true] {
+        for (name, value, _) in matching
+            .iter()
+            .chain(&inline)
+            .filter(|(_, _, is_important)| *is_important == important)
+        {
+            declarations.insert(name.to_ascii_lowercase(), value.trim().to_ascii_lowercase());
+        }
+    }
+
+    let mut style = ElementStyle::default();
+    for (name, value) in &declarations {
+        let value = value.as_str();
+        match name.as_str() {
+            "font-weight" => {
+                let bold = value == "bold"
+                    || value == "bolder"
+                    || value.parse::<u16>().is_ok_and(|weight| weight >= 600);
+                if bold {
+                    style.keys.push("bold".to_owned());
+                }
+            }
+            "font-style" if value == "italic" || value == "oblique" => {
+                style.keys.push("italic".to_owned())
+            }
+            "text-decoration" | "text-decoration-line" => {
+                if value.contains("underline") {
+                    style.keys.push("underline".to_owned());
+                }
+                if value.contains("line-through") {
+                    style.keys.push("line_through".to_owned());
+                }
+            }
+            "text-align" => style.alignment = Alignment::parse(value),
+            "display" => style.hidden = value == "none",
+            "text-indent" => style.text_indent = cells(value),
+            "margin-left" => style.margin_left = cells(value),
+            "margin" => {
+                // Top, right, bottom, left with the missing ones copied from the opposite side
+                let sides: Vec<&str> = value.split_whitespace().collect();
+                let left = match sides.len() {
+                    4 => sides[3],
+                    2 | 3 => sides[1],
+                    _ => sides.first().copied().unwrap_or(""),
+                };
+                style.margin_left = style.margin_left.or(cells(left));
+            }
+            "font-variant" | "font-variant-caps" => {
+                style.small_caps = Some(value.contains("small-caps"))
+            }
+            "direction" => style.direction = Direction::parse(value, String::new),
+            "page-break-before" | "break-before" => {
+                style.page_break_before = matches!(value, "always" | "page" | "left" | "right")
+            }
+            "color" if colors => {
+                if let Some(color) = color(value) {
+                    style.keys.push(format!("color:{}", color));
+                }
+            }
+            "background-color" | "background" if colors => {
+                if let Some(color) = color(value) {
+                    style.keys.push(format!("background:{}", color));
+                }
+            }
+            _ => {}
+        }
+    }
+    // Declarations come out of the map in any order, keep keys stable
+    style.keys.sort();
+    style
+}
+
+/// Length converted to terminal cells, a cell being about half an `em` wide.
+/// Negative lengths and percentages are ignored.
+fn cells(value: &str) -> Option<usize> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(value.len()),
+    );
+    let number: f32 = number.parse().ok()?;
+    let cells = match unit {
+        "em" | "rem" => number * 2.0,
+        "ch" | "ex" => number,
+        "px" => number / 8.0,
+        "pt" => number / 6.0,
+        "" if number == 0.0 => 0.0,
+        _ => return None,
+    };
+    Some(cells.max(0.0).round() as usize)
+}
+
+/// Colour as `#rrggbb`, from hex notation, `rgb()` or a basic colour name.
+fn color(value: &str) -> Option<String> {
+    if let Some(arguments) = value
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.split(')').next())
+    {
+        let rgb: Vec<u8> = arguments
+            .split(',')
+            .map(|part| part.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        if rgb.len() != 3 {
+            return None;
+        }
+        return Some(format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]));
+    }
+    // `background` may list more than a colour, only a leading one is used
+    let value = value.split_whitespace().next()?;
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        let rgb = match digits.len() {
+            3 => [digits[0] * 17, digits[1] * 17, digits[2] * 17],
+            6 => [
+                digits[0] * 16 + digits[1],
+                digits[2] * 16 + digits[3],
+                digits[4] * 16 + digits[5],
+            ],
+            _ => return None,
+        };
+        return Some(format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]));
+    }
+    let hex = match value {
+        "black" => "#000000",
+        "white" => "#ffffff",
+        "red" => "#ff0000",
+        "green" => "#008000",
+        "blue" => "#0000ff",
+        "yellow" => "#ffff00",
+        "gray" | "grey" => "#808080",
+        "silver" => "#c0c0c0",
+        "maroon" => "#800000",
+        "purple" => "#800080",
+        "navy" => "#000080",
+        "teal" => "#008080",
+        "orange" => "#ffa500",
+        _ => return None,
+    };
+    Some(hex.to_owned())
+}
+
+/// Element of the parsed document as seen by the selector matcher.
+struct Element<'d> {
+    doc: &'d Document,
+    id: NodeId,
+}
+
+impl simplecss::Element for Element<'_> {
+    fn parent_element(&self) -> Option<Self> {
+        let parent = self.doc.parent(self.id)?;
+        self.doc.name(parent)?;
+        Some(Element {
+            doc: self.doc,
+            id: parent,
+        })
+    }
+
+    fn prev_sibling_element(&self) -> Option<Self> {
+        let siblings = self.doc.children(self.doc.parent(self.id)?);
+        let position = siblings.iter().position(|id| *id == self.id)?;
+        siblings[..position]
+            .iter()
+            .rev()
+            .find(|id| self.doc.name(**id).is_some())
+            .map(|id| Element {
+                doc: self.doc,
+                id: *id,
+            })
+    }
+
+    fn has_local_name(&self, name: &str) -> bool {
+        self.doc
+            .name(self.id)
+            .is_some_and(|local| local.eq_ignore_ascii_case(name))
+    }
+
+    fn attribute_matches(&self, local_name: &str, operator: AttributeOperator<'_>) -> bool {
+        self.doc
+            .attr(self.id, &local_name.to_ascii_lowercase())
+            .is_some_and(|value| operator.matches(value))
+    }
+
+    fn pseudo_class_matches(&self, class: PseudoClass<'_>) -> bool {
+        match class {
+            PseudoClass::FirstChild => self.prev_sibling_element().is_none(),
+            PseudoClass::Link => {
+                self.has_local_name("a") && self.doc.attr(self.id, "href").is_some()
+            }
+            PseudoClass::Lang(lang) => {
+                let mut id = Some(self.id);
+                while let Some(current) = id {
+                    let doc = self.doc;
+                    if let Some(value) = doc.attr(current, "xml:lang").or(doc.attr(current, "lang"))
+                    {
+                        return AttributeOperator::StartsWith(lang).matches(value);
+                    }
+                    id = doc.parent(current);
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::align::Alignment;
+    use crate::css::{element_style, parse};
+    use crate::dom::Document;
+
+    #[test]
+    fn cascades_rules_by_specificity_and_inline_style() {
+        let doc = Document::parse(
+            r#"<div class="note"><p id="first" lang="pl">a</p><p style="text-indent: 0">b</p></div>"#,
+        );
+        let sheets = vec!["p { font-weight: bold; text-indent: 1em } \
+             .note p { text-align: center } \
+             #first { text-align: right; color: #f00 } \
+             p:lang(pl) { font-variant: small-caps } \
+             [id] { display: none }"
+            .to_owned()];
+        let stylesheet = parse(&sheets);
+        let paragraphs: Vec<_> = doc
+            .descendants(doc.root())
+            .into_iter()
+            .filter(|id| doc.name(*id) == Some("p"))
+            .collect();
+
+        let first = element_style(&stylesheet, &doc, paragraphs[0], true);
+        assert_eq!(first.alignment, Some(Alignment::Right));
+        assert_eq!(first.keys, vec!["bold", "color:#ff0000"]);
+        assert_eq!(first.small_caps, Some(true));
+        assert!(first.hidden);
+
+        let second = element_style(&stylesheet, &doc, paragraphs[1], false);
+        assert_eq!(second.alignment, Some(Alignment::Center));
+        assert_eq!(second.text_indent, Some(0));
+        assert!(!second.hidden);
+    }
+}
@@ -0,0 +1,384 @@
+use html_escape::decode_html_entities;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub type NodeId = usize;
+
+#[derive(Debug)]
+pub enum NodeData {
+    Document,
+    Element {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    Text(String),
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    pub data: NodeData,
+}
+
+/// Tree of an (X)HTML document. Parsing never fails: unknown constructs are skipped
+/// and unbalanced tags are closed the way browsers would close them.
+#[derive(Debug)]
+pub struct Document {
+    nodes: Vec<Node>,
+}
+
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+const RAW_TEXT_ELEMENTS: [&str; 4] = ["script", "style", "textarea", "title"];
+
+/// Elements which implicitly close an open `p`.
+const CLOSES_P: [&str; 24] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "fieldset",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+    "nav",
+];
+
+impl Document {
+    pub fn from_file(filepath: &str) -> io::Result<Self> {
+        let bytes = fs::read(Path::new(filepath))?;
+        Ok(Self::parse(&String::from_utf8_lossy(&bytes)))
+    }
+
+    pub fn parse(source: &str) -> Self {
+        let mut parser = Parser {
+            doc: Document {
+                nodes: vec![Node {
+                    parent: None,
+                    children: vec![],
+                    data: NodeData::Document,
+                }],
+            },
+            open: vec![0],
+        };
+        parser.run(source);
+        parser.doc
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id].children
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    /// Element name, `None` for text and the document node.
+    pub fn name(&self, id: NodeId) -> Option<&str> {
+        match &self.nodes[id].data {
+            NodeData::Element { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, id: NodeId) -> Option<&str> {
+        match &self.nodes[id].data {
+            NodeData::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn attr(&self, id: NodeId, attr: &str) -> Option<&str> {
+        match &self.nodes[id].data {
+            NodeData::Element { attrs, .. } => attrs
+                .iter()
+                .find(|(key, _)| key == attr)
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn attrs(&self, id: NodeId) -> &[(String, String)] {
+        match &self.nodes[id].data {
+            NodeData::Element { attrs, .. } => attrs,
+            _ => &[],
+        }
+    }
+
+    /// Concatenated text of all descendants.
+    pub fn text_content(&self, id: NodeId) -> String {
+        let mut out = String::new();
+        self.collect_text(id, &mut out);
+        out
+    }
+
+    fn collect_text(&self, id: NodeId, out: &mut String) {
+        match &self.nodes[id].data {
+            NodeData::Text(text) => out.push_str(text),
+            _ => {
+                for child in &self.nodes[id].children {
+                    self.collect_text(*child, out);
+                }
+            }
+        }
+    }
+
+    /// All nodes below `id` in document order, `id` included.
+    pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+        let mut out = vec![];
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            out.push(current);
+            stack.extend(self.nodes[current].children.iter().rev());
+        }
+        out
+    }
+
+    pub fn find_element(&self, name: &str) -> Option<NodeId> {
+        self.descendants(self.root())
+            .into_iter()
+            .find(|id| self.name(*id) == Some(name))
+    }
+}
+
+struct Parser {
+    doc: Document,
+    open: Vec<NodeId>,
+}
+
+impl Parser {
+    fn run(&mut self, source: &str) {
+        let mut rest = source;
+        while !rest.is_empty() {
+            let Some(lt) = rest.find('<') else {
+                self.push_text(&decode_html_entities(rest));
+                break;
+            };
+            if lt > 0 {
+                self.push_text(&decode_html_entities(&rest[..lt]));
+                rest = &rest[lt..];
+            }
+
+            if let Some(after) = rest.strip_prefix("<!--") {
+                rest = skip_past(after, "-->");
+            } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+                let end = after.find("]]>").unwrap_or(after.len());
+                self.push_text(&after[..end]);
+                rest = skip_past(after, "]]>");
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                rest = skip_past(rest, ">");
+            } else if let Some(after) = rest.strip_prefix("</") {
+                let end = after.find('>').unwrap_or(after.len());
+                let name = after[..end].trim().to_lowercase();
+                self.close(&name);
+                rest = skip_past(after, ">");
+            } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+                let (name, attrs, self_closing, after) = parse_tag(&rest[1..]);
+                rest = after;
+                let is_void = VOID_ELEMENTS.contains(&name.as_str());
+                let is_raw = RAW_TEXT_ELEMENTS.contains(&name.as_str());
+                self.open_element(name.clone(), attrs);
+                if self_closing || is_void {
+                    self.open.pop();
+                } else if is_raw {
+                    let closing = format!("</{}", name);
+                    let end = find_ignore_case(rest, &closing).unwrap_or(rest.len());
+                    self.push_text(&rest[..end]);
+                    self.open.pop();
+                    rest = skip_past(&rest[end..], ">");
+                }
+            } else {
+                self.push_text("<");
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    fn current(&self) -> NodeId {
+        *self.open.last().unwrap()
+    }
+
+    fn append(&mut self, data: NodeData) -> NodeId {
+        let parent = self.current();
+        let id = self.doc.nodes.len();
+        self.doc.nodes.push(Node {
+            parent: Some(parent),
+            children: vec![],
+            data,
+        });
+        self.doc.nodes[parent].children.push(id);
+        id
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let parent = self.current();
+        if let Some(last) = self.doc.nodes[parent].children.last().copied() {
+            if let NodeData::Text(existing) = &mut self.doc.nodes[last].data {
+                existing.push_str(text);
+                return;
+            }
+        }
+        self.append(NodeData::Text(text.to_owned()));
+    }
+
+    fn open_element(&mut self, name: String, attrs: Vec<(String, String)>) {
+        self.close_implied(&name);
+        let id = self.append(NodeData::Element { name, attrs });
+        self.open.push(id);
+    }
+
+    /// Closes elements which can't contain the element being opened, e.g. `<p>a<p>b`.
+    fn close_implied(&mut self, name: &str) {
+        let closes: &[&str] = match name {
+            "li" => &["li"],
+            "dt" | "dd" => &["dt", "dd"],
+            "tr" => &["tr", "td", "th"],
+            "td" | "th" => &["td", "th"],
+            "option" => &["option"],
+            _ => &[],
+        };
+        let scope_boundaries: &[&str] = match name {
+            "li" => &["ul", "ol"],
+            "dt" | "dd" => &["dl"],
+            "tr" | "td" | "th" => &["table"],
+            _ => &[],
+        };
+        for closed in closes {
+            self.close_in_scope(closed, scope_boundaries);
+        }
+        if CLOSES_P.contains(&name) {
+            self.close_in_scope("p", &[]);
+        }
+    }
+
+    fn close_in_scope(&mut self, name: &str, boundaries: &[&str]) {
+        for (depth, id) in self.open.iter().enumerate().rev() {
+            match self.doc.name(*id) {
+                Some(open) if open == name => {
+                    self.open.truncate(depth);
+                    return;
+                }
+                Some(open) if boundaries.contains(&open) => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        // Stray closing tags without a matching open element are ignored
+        if let Some(depth) = self
+            .open
+            .iter()
+            .rposition(|id| self.doc.name(*id) == Some(name))
+        {
+            self.open.truncate(depth);
+        }
+    }
+}
+
+fn skip_past<'s>(text: &'s str, pattern: &str) -> &'s str {
+    match text.find(pattern) {
+        Some(i) => &text[i + pattern.len()..],
+        None => "",
+    }
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    let needle = needle.to_ascii_lowercase();
+    haystack.char_indices().map(|(i, _)| i).find(|i| {
+        haystack.as_bytes()[*i..]
+            .iter()
+            .take(needle.len())
+            .map(|b| b.to_ascii_lowercase())
+            .eq(needle.bytes())
+    })
+}
+
+/// Parses `name attr="value" ...>` returning the name, attributes, whether the tag was
+/// self closing and the text after the tag.
+fn parse_tag(text: &str) -> (String, Vec<(String, String)>, bool, &str) {
+    let name_end = text
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(text.len());
+    let name = text[..name_end].to_lowercase();
+    let mut rest = &text[name_end..];
+    let mut attrs = vec![];
+    let mut self_closing = false;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            rest = after;
+            break;
+        }
+        if let Some(after) = rest.strip_prefix('/') {
+            self_closing = after.trim_start().starts_with('>') || after.is_empty();
+            rest = after;
+            continue;
+        }
+
+        let key_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_lowercase();
+        rest = rest[key_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            match after.chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => {
+                    let end = after[1..].find(quote).map(|i| i + 1).unwrap_or(after.len());
+                    value = decode_html_entities(&after[1..end]).into_owned();
+                    rest = after.get(end + 1..).unwrap_or("");
+                }
+                _ => {
+                    let end = after
+                        .find(|c: char| c.is_whitespace() || c == '>')
+                        .unwrap_or(after.len());
+                    value = decode_html_entities(&after[..end]).into_owned();
+                    rest = &after[end..];
+                }
+            }
+        }
+        if key.is_empty() {
+            // Garbage such as a lone `=`, skip a char so we always make progress
+            rest = rest.get(1..).unwrap_or("");
+            continue;
+        }
+        attrs.push((key, value));
+    }
+
+    (name, attrs, self_closing, rest)
+}
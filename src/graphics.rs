@@ -0,0 +1,271 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{Rgba, RgbaImage};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{stdin, stdout, Write};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+/// Size of a terminal cell in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Common size of a cell when the terminal doesn't tell.
+const DEFAULT_CELL: CellSize = CellSize {
+    width: 8,
+    height: 16,
+};
+
+/// How images can be drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Graphics {
+    /// Only the `alt` text of images is shown.
+    None,
+    /// Two pixels per cell with coloured `▀`, needs truecolor.
+    HalfBlocks,
+    Kitty(CellSize),
+    Sixel(CellSize),
+}
+
+impl Graphics {
+    /// Size of cells when the terminal draws pixels itself.
+    pub fn cell_size(&self) -> Option<CellSize> {
+        match self {
+            Graphics::Kitty(cell) | Graphics::Sixel(cell) => Some(*cell),
+            _ => None,
+        }
+    }
+}
+
+/// How long the terminal gets to answer the queries.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Asks the terminal whether it speaks the Kitty graphics protocol or Sixel and how big
+/// its cells are. Raw mode has to be enabled and nothing else may be reading the input.
+/// Terminals answering neither fall back to half blocks when they're truecolor.
+pub fn detect() -> Graphics {
+    // Kitty answers the graphics query, every terminal answers the primary device
+    // attributes query, so its answer ends the replies
+    let mut out = stdout();
+    let query = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[16t\x1b[c";
+    if out.write_all(query.as_bytes()).and(out.flush()).is_err() {
+        return fallback();
+    }
+
+    match read_reply(Instant::now() + QUERY_TIMEOUT) {
+        Some(reply) => parse_reply(&reply),
+        None => fallback(),
+    }
+}
+
+/// Reads the terminal's replies until the device attributes one, giving up at
+/// `deadline`. Input is only read when it's there, so nothing is left waiting on it
+/// to take keys from the event reader.
+fn read_reply(deadline: Instant) -> Option<String> {
+    let fd = stdin().as_raw_fd();
+    let mut reply = String::new();
+    while device_attributes(&reply).is_none() {
+        let left = deadline.checked_duration_since(Instant::now())?;
+        let mut poll = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: one valid descriptor is polled
+        let ready = unsafe { libc::poll(&mut poll, 1, left.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            return None;
+        }
+        // One byte at a time past std's buffered stdin, keys typed after the replies stay
+        // for the event reader
+        let mut byte = 0u8;
+        // SAFETY: one byte is read into a local byte
+        if unsafe { libc::read(fd, (&mut byte as *mut u8).cast(), 1) } != 1 {
+            return None;
+        }
+        reply.push(byte as char);
+    }
+    Some(reply)
+}
+
+fn fallback() -> Graphics {
+    let truecolor = std::env::var("COLORTERM")
+        .is_ok_and(|colorterm| colorterm == "truecolor" || colorterm == "24bit");
+    match truecolor {
+        true => Graphics::HalfBlocks,
+        false => Graphics::None,
+    }
+}
+
+/// Graphics supported according to the terminal's replies to the queries of `detect`.
+fn parse_reply(reply: &str) -> Graphics {
+    let cell = cell_size(reply).unwrap_or(DEFAULT_CELL);
+    if reply.contains("\x1b_Gi=31;OK") {
+        return Graphics::Kitty(cell);
+    }
+    match device_attributes(reply) {
+        Some(attributes) if attributes.split(';').skip(1).any(|a| a == "4") => {
+            Graphics::Sixel(cell)
+        }
+        _ => fallback(),
+    }
+}
+
+/// Parameters of the primary device attributes reply, `ESC [ ? 62 ; 4 c`.
+fn device_attributes(reply: &str) -> Option<&str> {
+    let start = reply.find("\x1b[?")? + 3;
+    let length = reply[start..].find('c')?;
+    Some(&reply[start..start + length])
+}
+
+/// Cell size from the reply to `ESC [ 16 t`, `ESC [ 6 ; height ; width t`.
+fn cell_size(reply: &str) -> Option<CellSize> {
+    let start = reply.find("\x1b[6;")? + 4;
+    let length = reply[start..].find('t')?;
+    let (height, width) = reply[start..start + length].split_once(';')?;
+    let cell = CellSize {
+        width: width.parse().ok()?,
+        height: height.parse().ok()?,
+    };
+    (cell.width > 0 && cell.height > 0).then_some(cell)
+}
+
+/// Bytes of a Kitty graphics payload sent at once, the protocol's limit.
+const KITTY_CHUNK: usize = 4096;
+
+/// Kitty graphics command placing the image over `columns` by `rows` cells at the cursor.
+/// Images are put under the text so popups stay readable, and one with the same `id`
+/// replaces the image drawn before.
+pub fn kitty(image: &RgbaImage, id: u32, columns: u16, rows: u16) -> String {
+    let payload = STANDARD.encode(image.as_raw());
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut command = String::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = (index + 1 < chunks.len()) as u8;
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        match index {
+            0 => write!(
+                command,
+                "\x1b_Ga=T,f=32,s={},v={},i={},c={},r={},z=-1,C=1,q=2,m={};{}\x1b\\",
+                image.width(),
+                image.height(),
+                id,
+                columns,
+                rows,
+                more,
+                chunk
+            ),
+            _ => write!(command, "\x1b_Gm={};{}\x1b\\", more, chunk),
+        }
+        .unwrap();
+    }
+    command
+}
+
+/// Kitty graphics command removing all images from the screen.
+pub const KITTY_CLEAR: &str = "\x1b_Ga=d,q=2\x1b\\";
+
+/// Sixel sequence drawing the image with colours of a 6×6×6 cube. Transparent pixels
+/// are left to the terminal background.
+pub fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let mut sequence = format!("\x1bP0;1q\"1;1;{};{}", width, height);
+
+    let mut used = [false; 216];
+    image
+        .pixels()
+        .filter_map(palette_index)
+        .for_each(|index| used[index] = true);
+    for (index, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let (r, g, b) = (index / 36, index / 6 % 6, index % 6);
+        write!(sequence, "#{};2;{};{};{}", index, r * 20, g * 20, b * 20).unwrap();
+    }
+
+    // Every sixel character covers six rows of one colour in a column
+    for band in (0..height).step_by(6) {
+        let mut colors: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for dy in 0..6.min(height - band) {
+            for x in 0..width {
+                if let Some(index) = palette_index(image.get_pixel(x, band + dy)) {
+                    colors.entry(index).or_insert(vec![0; width as usize])[x as usize] |= 1 << dy;
+                }
+            }
+        }
+        for (position, (index, bits)) in colors.iter().enumerate() {
+            if position > 0 {
+                // Back to the start of the band for the next colour
+                sequence.push('$');
+            }
+            write!(sequence, "#{}", index).unwrap();
+            run_length(&mut sequence, bits);
+        }
+        sequence.push('-');
+    }
+    sequence.push_str("\x1b\\");
+    sequence
+}
+
+/// Appends sixel characters for `bits`, repeated ones as `!count char`.
+fn run_length(sequence: &mut String, bits: &[u8]) {
+    let mut columns = bits.iter().peekable();
+    while let Some(bit) = columns.next() {
+        let mut count = 1;
+        while columns.next_if_eq(&bit).is_some() {
+            count += 1;
+        }
+        let char = (63 + bit) as char;
+        match count {
+            1..=3 => (0..count).for_each(|_| sequence.push(char)),
+            _ => write!(sequence, "!{}{}", count, char).unwrap(),
+        }
+    }
+}
+
+/// Colour of the 6×6×6 cube closest to a pixel, `None` when it's mostly transparent.
+fn palette_index(pixel: &Rgba<u8>) -> Option<usize> {
+    let [r, g, b, a] = pixel.0;
+    let level = |channel: u8| (channel as usize * 5 + 127) / 255;
+    (a >= 128).then(|| level(r) * 36 + level(g) * 6 + level(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::{parse_reply, sixel, CellSize, Graphics};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn replies_tell_the_protocol_and_cell_size() {
+        assert_eq!(
+            parse_reply("\x1b_Gi=31;OK\x1b\\\x1b[6;20;10t\x1b[?62;22c"),
+            Graphics::Kitty(CellSize {
+                width: 10,
+                height: 20
+            })
+        );
+        assert_eq!(
+            parse_reply("\x1b[?62;4;22c"),
+            Graphics::Sixel(CellSize {
+                width: 8,
+                height: 16
+            })
+        );
+    }
+
+    #[test]
+    fn sixel_bands_are_run_length_encoded() {
+        let mut image = RgbaImage::new(5, 2);
+        for x in 0..5 {
+            image.put_pixel(x, 0, Rgba([255, 0, 0, 255]));
+        }
+        image.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
+
+        assert_eq!(
+            sixel(&image),
+            "\x1bP0;1q\"1;1;5;2#5;2;0;0;100#180;2;100;0;0#5A!4?$#180!5@-\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use crate::line::Line;
+
+/// Minimal description of a language, enough to colour keywords, strings, numbers and
+/// comments.
+struct Syntax {
+    keywords: &'static [&'static str],
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+}
+
+const C_LIKE_COMMENTS: Option<(&str, &str)> = Some(("/*", "*/"));
+
+fn syntax(language: &str) -> Option<Syntax> {
+    let syntax = match language.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => Syntax {
+            keywords: &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                "enum", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+                "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+                "trait", "true", "type", "unsafe", "use", "where", "while",
+            ],
+            line_comment: "//",
+            block_comment: C_LIKE_COMMENTS,
+        },
+        "c" | "cpp" | "c++" | "h" | "hpp" | "cs" | "csharp" | "java" | "kotlin" | "swift" => {
+            Syntax {
+                keywords: &[
+                    "auto",
+                    "bool",
+                    "break",
+                    "case",
+                    "catch",
+                    "char",
+                    "class",
+                    "const",
+                    "continue",
+                    "default",
+                    "delete",
+                    "do",
+                    "double",
+                    "else",
+                    "enum",
+                    "extends",
+                    "false",
+                    "final",
+                    "float",
+                    "for",
+                    "if",
+                    "implements",
+                    "import",
+                    "include",
+                    "int",
+                    "interface",
+                    "long",
+                    "namespace",
+                    "new",
+                    "null",
+                    "nullptr",
+                    "package",
+                    "private",
+                    "protected",
+                    "public",
+                    "return",
+                    "short",
+                    "sizeof",
+                    "static",
+                    "struct",
+                    "switch",
+                    "template",
+                    "this",
+                    "throw",
+                    "true",
+                    "try",
+                    "typedef",
+                    "unsigned",
+                    "using",
+                    "var",
+                    "virtual",
+                    "void",
+                    "while",
+                ],
+                line_comment: "//",
+                block_comment: C_LIKE_COMMENTS,
+            }
+        }
+        "js" | "javascript" | "ts" | "typescript" | "jsx" | "tsx" => Syntax {
+            keywords: &[
+                "async",
+                "await",
+                "break",
+                "case",
+                "catch",
+                "class",
+                "const",
+                "continue",
+                "default",
+                "else",
+                "export",
+                "extends",
+                "false",
+                "finally",
+                "for",
+                "from",
+                "function",
+                "if",
+                "import",
+                "in",
+                "instanceof",
+                "interface",
+                "let",
+                "new",
+                "null",
+                "of",
+                "return",
+                "switch",
+                "this",
+                "throw",
+                "true",
+                "try",
+                "type",
+                "typeof",
+                "undefined",
+                "var",
+                "while",
+                "yield",
+            ],
+            line_comment: "//",
+            block_comment: C_LIKE_COMMENTS,
+        },
+        "go" | "golang" => Syntax {
+            keywords: &[
+                "break",
+                "case",
+                "chan",
+                "const",
+                "continue",
+                "defer",
+                "else",
+                "false",
+                "for",
+                "func",
+                "go",
+                "if",
+                "import",
+                "interface",
+                "map",
+                "nil",
+                "package",
+                "range",
+                "return",
+                "select",
+                "struct",
+                "switch",
+                "true",
+                "type",
+                "var",
+            ],
+            line_comment: "//",
+            block_comment: C_LIKE_COMMENTS,
+        },
+        "python" | "py" => Syntax {
+            keywords: &[
+                "and", "as", "break", "class", "continue", "def", "elif", "else", "except",
+                "False", "finally", "for", "from", "if", "import", "in", "is", "lambda", "None",
+                "not", "or", "pass", "raise", "return", "self", "True", "try", "while", "with",
+                "yield",
+            ],
+            line_comment: "#",
+            block_comment: None,
+        },
+        "sh" | "bash" | "shell" | "zsh" | "console" => Syntax {
+            keywords: &[
+                "case", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for",
+                "function", "if", "in", "local", "return", "then", "while",
+            ],
+            line_comment: "#",
+            block_comment: None,
+        },
+        "sql" => Syntax {
+            keywords: &[
+                "and", "as", "by", "create", "delete", "from", "group", "insert", "into", "join",
+                "not", "null", "on", "or", "order", "select", "set", "table", "update", "values",
+                "where", "AND", "AS", "BY", "CREATE", "DELETE", "FROM", "GROUP", "INSERT", "INTO",
+                "JOIN", "NOT", "NULL", "ON", "OR", "ORDER", "SELECT", "SET", "TABLE", "UPDATE",
+                "VALUES", "WHERE",
+            ],
+            line_comment: "--",
+            block_comment: C_LIKE_COMMENTS,
+        },
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// Language named by a `language-xxx` or `lang-xxx` class.
+pub fn language_from_class(class: &str) -> Option<&str> {
+    class.split_whitespace().find_map(|class| {
+        class
+            .strip_prefix("language-")
+            .or_else(|| class.strip_prefix("lang-"))
+    })
+}
+
+/// Colours code lines, keeping their original styles underneath. Unknown languages are
+/// returned untouched.
+pub fn highlight(lines: Vec<Line>, language: &str) -> Vec<Line> {
+    let Some(syntax) = syntax(language) else {
+        return lines;
+    };
+    let mut in_block_comment = false;
+    lines
+        .iter()
+        .map(|line| {
+            let mut highlighted = Line::new();
+            for span in &line.spans {
+                for (text, key) in tokenize(&span.text, &syntax, &mut in_block_comment) {
+                    let mut style = span.style.clone();
+                    if let Some(key) = key {
+                        style.keys.push(key.to_owned());
+                    }
+                    highlighted.push(text, &style);
+                }
+            }
+            highlighted
+        })
+        .collect()
+}
+
+fn tokenize<'t>(
+    text: &'t str,
+    syntax: &Syntax,
+    in_block_comment: &mut bool,
+) -> Vec<(&'t str, Option<&'static str>)> {
+    let mut tokens = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        if *in_block_comment {
+            let (_, end) = syntax.block_comment.unwrap();
+            let length = match rest.find(end) {
+                Some(i) => {
+                    *in_block_comment = false;
+                    i + end.len()
+                }
+                None => rest.len(),
+            };
+            tokens.push((&rest[..length], Some("code_comment")));
+            rest = &rest[length..];
+            continue;
+        }
+
+        if let Some((start, _)) = syntax
+            .block_comment
+            .filter(|(start, _)| rest.starts_with(start))
+        {
+            *in_block_comment = true;
+            tokens.push((&rest[..start.len()], Some("code_comment")));
+            rest = &rest[start.len()..];
+            continue;
+        }
+
+        if rest.starts_with(syntax.line_comment) {
+            tokens.push((rest, Some("code_comment")));
+            break;
+        }
+
+        let first = rest.chars().next().unwrap();
+        let (length, key) = if first == '"' || first == '\'' {
+            (string_length(rest, first), Some("code_string"))
+        } else if first.is_ascii_digit() {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .unwrap_or(rest.len());
+            (length, Some("code_number"))
+        } else if first.is_alphabetic() || first == '_' {
+            let length = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let key = match syntax.keywords.contains(&&rest[..length]) {
+                true => Some("code_keyword"),
+                false => None,
+            };
+            (length, key)
+        } else {
+            (first.len_utf8(), None)
+        };
+        tokens.push((&rest[..length], key));
+        rest = &rest[length..];
+    }
+    tokens
+}
+
+/// Length of a quoted string including both quotes, or up to the end of the line.
+fn string_length(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c if c == quote && !escaped => return i + c.len_utf8(),
+            _ => escaped = false,
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::highlight::highlight;
+    use crate::line::{Line, Style};
+
+    #[test]
+    fn highlights_keywords_strings_and_comments() {
+        let lines = highlight(
+            vec![Line::from("let s = \"fn\"; // note"), Line::from("x /* a")],
+            "rust",
+        );
+        let keys: Vec<(String, Option<String>)> = lines[0]
+            .spans
+            .iter()
+            .map(|span| (span.text.clone(), span.style.keys.last().cloned()))
+            .collect();
+        assert_eq!(keys[0], ("let".to_owned(), Some("code_keyword".to_owned())));
+        assert!(keys.contains(&("\"fn\"".to_owned(), Some("code_string".to_owned()))));
+        assert_eq!(keys.last().unwrap().1.as_deref(), Some("code_comment"));
+        assert_eq!(lines[1].spans[0].style, Style::default());
+    }
+}
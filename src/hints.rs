@@ -0,0 +1,38 @@
+/// Labels for `count` links made of `alphabet` letters, all of the same length so no
+/// label is the beginning of another one. There are none when the alphabet can't tell
+/// the links apart.
+pub fn labels(count: usize, alphabet: &str) -> Vec<String> {
+    let letters: Vec<char> = alphabet.chars().collect();
+    if count == 0 || letters.is_empty() || (letters.len() == 1 && count > 1) {
+        return vec![];
+    }
+    let mut length = 1;
+    while letters.len().pow(length) < count {
+        length += 1;
+    }
+
+    (0..count)
+        .map(|mut index| {
+            let mut label = vec![letters[0]; length as usize];
+            for slot in label.iter_mut().rev() {
+                *slot = letters[index % letters.len()];
+                index /= letters.len();
+            }
+            label.into_iter().collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hints::labels;
+
+    #[test]
+    fn labels_grow_only_when_letters_run_out() {
+        assert_eq!(labels(3, "sad"), vec!["s", "a", "d"]);
+        assert_eq!(labels(4, "sad"), vec!["ss", "sa", "sd", "as"]);
+        assert!(labels(0, "sad").is_empty());
+        assert_eq!(labels(1, "s"), vec!["s"]);
+        assert!(labels(2, "s").is_empty());
+    }
+}
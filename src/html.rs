@@ -1,169 +1,1537 @@
-use crate::styler::Styler;
-use crate::styler::TagStyler;
-use regex::Regex;
-use regex::RegexSet;
-use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
+use crate::align::Alignment;
+use crate::bidi::Direction;
+use crate::css::{self, element_style, stylesheets};
+use crate::dom::{Document, NodeId};
+use crate::graphics::Graphics;
+use crate::highlight::{highlight, language_from_class};
+use crate::hyphen::Hyphenation;
+use crate::layout::{layout, Block, Indented, Overflow, Paragraph, Preformatted};
+use crate::line::{Line, Style};
+use crate::list::{bullet, number, List, ListItem};
+use crate::math::{self, MathLayout};
+use crate::picture::Picture;
+use crate::ruby::{pad_base, RubyLayout};
+use crate::svg;
+use crate::table::{Cell, Row, Table};
+use crate::wrap::{width, ZERO_WIDTH_SPACE};
+use simplecss::StyleSheet;
 use std::path::Path;
 
-pub struct HtmlToLine<'a> {
-    styler: &'a TagStyler,
+pub struct HtmlToLine;
+
+pub struct LayoutOptions<'a> {
+    pub max_chars_in_line: u16,
+    /// Language of the book, elements with `lang` or `xml:lang` override it.
+    pub language: Option<String>,
+    pub hyphenation: Option<&'a Hyphenation>,
+    pub alignment: Alignment,
+    /// Used for headings unless they set their own `text-align`.
+    pub heading_alignment: Alignment,
+    pub pre_overflow: Overflow,
+    /// Empty lines between paragraphs and other blocks.
+    pub paragraph_spacing: usize,
+    /// Indentation of the first line of a paragraph following another paragraph.
+    pub first_line_indent: usize,
+    /// Marker drawn in place of `hr`.
+    pub scene_break: &'a str,
+    /// Whether colours from the book's CSS are shown.
+    pub book_colors: bool,
+    /// How images are drawn, `Graphics::None` shows their `alt` text.
+    pub images: Graphics,
+    pub math: MathLayout,
+    /// Direction of paragraphs which don't set their own, from the book's spine.
+    pub direction: Direction,
+    /// Whether lines are put in the order they're drawn in with the Unicode
+    /// Bidirectional Algorithm, terminals doing it themselves need it off.
+    pub bidi: bool,
+    pub ruby: RubyLayout,
+    /// Classes styled by the theme, given to the styler as `.class` keys.
+    pub classes: &'a [String],
+}
+
+impl<'a> LayoutOptions<'a> {
+    /// Options with every optional feature turned off and text aligned left.
+    pub fn new(max_chars_in_line: u16) -> Self {
+        Self {
+            max_chars_in_line,
+            language: None,
+            hyphenation: None,
+            alignment: Alignment::Left,
+            heading_alignment: Alignment::Left,
+            pre_overflow: Overflow::Wrap,
+            paragraph_spacing: 0,
+            first_line_indent: 0,
+            scene_break: "* * *",
+            book_colors: false,
+            images: Graphics::None,
+            math: MathLayout::Linear,
+            direction: Direction::Ltr,
+            bidi: false,
+            ruby: RubyLayout::Inline,
+            classes: &[],
+        }
+    }
 }
 
-impl<'a> HtmlToLine<'a> {
-    pub fn as_lines<S: Styler>(
-        filepath: &str,
-        styler: &'a S,
-        max_chars_in_line: u16,
-    ) -> Vec<String> {
+impl HtmlToLine {
+    pub fn as_lines(filepath: &str, options: &LayoutOptions) -> Vec<Line> {
+        Self::render(filepath, None, options)
+    }
+
+    /// Lines of the note with the given id, or of the paragraph holding it.
+    pub fn note_as_lines(filepath: &str, id: &str, options: &LayoutOptions) -> Vec<Line> {
+        Self::render(filepath, Some(id), options)
+    }
+
+    fn render(filepath: &str, fragment: Option<&str>, options: &LayoutOptions) -> Vec<Line> {
         let html_path = Path::new(filepath);
 
         if !html_path.exists() {
             panic!("File at: '{}' do not exists", &filepath);
         }
 
-        let html_file =
-            File::open(html_path).expect(&format!("File at: '{}' cannot be open", &filepath));
-
-        let lines = BufReader::new(html_file).lines();
-        let tags = [
-            "p",
-            "h1",
-            "h2",
-            "h3",
-            "h4",
-            "h5",
-            "h6",
-            "div",
-            "a",
-            "i",
-            "li",
-            "em",
-            "q",
-            "dt",
-            "dd",
-            "blockquote",
-            "b",
-            "span",
-        ];
-        let mut regexes = Vec::with_capacity(tags.len());
+        let document = Document::from_file(filepath)
+            .unwrap_or_else(|_| panic!("File at: '{}' cannot be open", &filepath));
 
-        for elem in tags.iter() {
-            regexes.push(format!("<{}.*?>(.*?)</{}>", elem, elem));
+        let sheets = stylesheets(&document, html_path);
+        let stylesheet = css::parse(&sheets);
+        let mut renderer = TreeRenderer {
+            doc: &document,
+            path: filepath,
+            options,
+            blocks: vec![],
+            current: Line::new(),
+            style: Style {
+                lang: options.language.clone(),
+                ..Style::default()
+            },
+            alignment: None,
+            list_depth: 0,
+            after_text: false,
+            stylesheet: &stylesheet,
+            indent: None,
+            small_caps: false,
+            anchors: vec![],
+            direction: None,
+            verse: false,
+        };
+        match fragment {
+            None => renderer.walk(document.root()),
+            Some(fragment) => {
+                let Some(note) = note_root(&document, fragment) else {
+                    return vec![];
+                };
+                // Notes are hidden from the flow, so their content is walked directly
+                renderer.element(note, document.name(note).unwrap_or(""));
+            }
         }
+        renderer.flush();
+
+        let mut extracted_lines: Vec<Line> = vec![];
+        layout(
+            &renderer.blocks,
+            options.max_chars_in_line.into(),
+            options.paragraph_spacing,
+            options,
+            &mut extracted_lines,
+        );
+        extracted_lines
+    }
+}
 
-        let regex_set = RegexSet::new(regexes.iter()).unwrap();
-        let mut extracted_lines: Vec<String> = vec![];
+/// Elements whose content is never shown.
+const HIDDEN_TAGS: [&str; 5] = ["head", "script", "style", "title", "template"];
 
-        for line in lines {
-            let line = line.unwrap();
-            let mut tag_content = line.to_owned();
-            let mut output = line.to_owned();
-            let mut i = 0;
-            loop {
-                let total_matches = regex_set
-                    .matches(&tag_content)
-                    .into_iter()
-                    .collect::<Vec<_>>();
+/// Elements which start their own paragraph.
+const BLOCK_TAGS: [&str; 35] = [
+    "html",
+    "body",
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "div",
+    "li",
+    "ul",
+    "ol",
+    "dl",
+    "dt",
+    "dd",
+    "blockquote",
+    "section",
+    "article",
+    "aside",
+    "header",
+    "footer",
+    "nav",
+    "figure",
+    "figcaption",
+    "table",
+    "tr",
+    "menu",
+    "pre",
+    "hr",
+    "img",
+    "image",
+    "svg",
+    // FictionBook poems
+    "poem",
+    "stanza",
+];
 
-                if total_matches.len() == 0 {
-                    if i == 0 {
-                        tag_content = String::new();
+/// Elements passed to the styler, everything else is rendered unstyled.
+const STYLED_TAGS: [&str; 22] = [
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "div",
+    "a",
+    "i",
+    "li",
+    "em",
+    "q",
+    "dt",
+    "dd",
+    "blockquote",
+    "b",
+    "span",
+    "code",
+    "kbd",
+    "samp",
+    "tt",
+];
+
+struct TreeRenderer<'d> {
+    doc: &'d Document,
+    /// Path of the HTML file, resources are relative to it.
+    path: &'d str,
+    options: &'d LayoutOptions<'d>,
+    blocks: Vec<Block>,
+    current: Line,
+    style: Style,
+    /// Alignment set by an enclosing element, inherited like CSS `text-align`.
+    alignment: Option<Alignment>,
+    /// Number of lists enclosing the content, picks bullets of unordered lists.
+    list_depth: usize,
+    /// Whether the last block is a paragraph of text, the next one gets its first line
+    /// indented.
+    after_text: bool,
+    stylesheet: &'d StyleSheet<'d>,
+    /// First line indentation set by CSS `text-indent`, inherited.
+    indent: Option<usize>,
+    /// Text is uppercased by CSS `font-variant: small-caps`.
+    small_caps: bool,
+    /// Ids of elements started since the last text, given to the next text pushed.
+    anchors: Vec<String>,
+    /// Direction set by a `dir` attribute or CSS, inherited.
+    direction: Option<Direction>,
+    /// Content is a poem, its line breaks are kept.
+    verse: bool,
+}
+
+/// Indentation of `blockquote` content.
+const QUOTE_INDENT: usize = 4;
+
+/// Number of lines of a paragraph broken with `br` from which they're taken as verses.
+const VERSE_LINES: usize = 3;
+
+/// Widest line of a paragraph taken as verses, hand broken prose has longer ones.
+const VERSE_WIDTH: usize = 60;
+
+impl<'d> TreeRenderer<'d> {
+    fn walk(&mut self, id: NodeId) {
+        let doc = self.doc;
+        if let Some(text) = doc.text(id) {
+            self.push_collapsed(text);
+            return;
+        }
+
+        let name = doc.name(id).unwrap_or("");
+        if HIDDEN_TAGS.contains(&name) {
+            return;
+        }
+        let css = element_style(self.stylesheet, doc, id, self.options.book_colors);
+        if css.hidden || is_note(doc, id) {
+            return;
+        }
+        if name == "a" && has_epub_type(doc, id, "noteref") {
+            self.note_reference(id);
+            return;
+        }
+
+        let outer_style = self.style.clone();
+        let outer_alignment = self.alignment;
+        let outer_indent = self.indent;
+        let outer_small_caps = self.small_caps;
+        let outer_direction = self.direction;
+        let outer_verse = self.verse;
+        if let Some(lang) = doc.attr(id, "xml:lang").or_else(|| doc.attr(id, "lang")) {
+            self.style.lang = Some(lang.to_owned());
+        }
+        let alignment = css
+            .alignment
+            .or_else(|| doc.attr(id, "align").and_then(Alignment::parse));
+        match alignment {
+            Some(alignment) => self.alignment = Some(alignment),
+            None if is_heading(name) => self.alignment = Some(self.options.heading_alignment),
+            None => {}
+        }
+        if css.text_indent.is_some() {
+            self.indent = css.text_indent;
+        }
+        if let Some(small_caps) = css.small_caps {
+            self.small_caps = small_caps;
+        }
+        let direction = doc
+            .attr(id, "dir")
+            .and_then(|dir| Direction::parse(dir, || doc.text_content(id)))
+            .or(css.direction);
+        if direction.is_some() {
+            self.direction = direction;
+        }
+        if is_verse(doc, id) {
+            self.verse = true;
+        }
+        if STYLED_TAGS.contains(&name) {
+            self.style.keys.push(name.to_owned());
+        }
+        self.style.keys.extend(css.keys);
+        let classes = doc.attr(id, "class").unwrap_or("").split_whitespace();
+        for class in classes.filter(|class| self.options.classes.iter().any(|c| c == class)) {
+            self.style.keys.push(format!(".{}", class));
+        }
+        if let Some(id) = doc.attr(id, "id").or_else(|| doc.attr(id, "name")) {
+            self.anchors.push(id.to_owned());
+        }
+        if name == "a" {
+            self.style.link = doc.attr(id, "href").map(str::to_owned);
+        }
+
+        // Paragraphs of a poem and elements marked as its lines are its verses, kept
+        // together in the paragraph of the stanza
+        let is_verse_line = outer_verse
+            && (matches!(name, "p" | "div") || has_verse_markup(doc, id, &["line"]))
+            && !is_verse(doc, id);
+        if is_verse_line {
+            let indent = css.margin_left.unwrap_or(0) + css.text_indent.unwrap_or(0);
+            self.current.push(&"\u{A0}".repeat(indent), &self.style);
+            self.element(id, name);
+            self.line_break();
+        }
+        let is_block = BLOCK_TAGS.contains(&name) && !is_verse_line;
+        if is_block {
+            self.flush();
+            if css.page_break_before {
+                self.push_block(Block::PageBreak);
+            }
+        }
+        let margin = match name {
+            "blockquote" => Some(css.margin_left.unwrap_or(QUOTE_INDENT)),
+            _ => css.margin_left.filter(|margin| is_block && *margin > 0),
+        };
+        match margin {
+            Some(indent) => {
+                let mut renderer = self.nested(self.alignment);
+                renderer.element(id, name);
+                renderer.flush();
+                self.push_block(Block::Indented(Indented {
+                    indent,
+                    blocks: renderer.blocks,
+                }));
+            }
+            None if is_verse_line => {}
+            None => self.element(id, name),
+        }
+        if is_block {
+            self.flush();
+        }
+
+        self.style = outer_style;
+        self.alignment = outer_alignment;
+        self.indent = outer_indent;
+        self.small_caps = outer_small_caps;
+        self.direction = outer_direction;
+        self.verse = outer_verse;
+    }
+
+    /// Content of an element, its own style already applied.
+    fn element(&mut self, id: NodeId, name: &str) {
+        match name {
+            "table" => {
+                let table = self.table(id);
+                self.push_block(Block::Table(table));
+            }
+            "ul" | "ol" | "menu" => {
+                let list = self.list(id);
+                self.push_block(Block::List(list));
+            }
+            "pre" => {
+                let preformatted = self.preformatted(id);
+                self.push_block(Block::Preformatted(preformatted));
+            }
+            "dl" => self.definitions(id),
+            "hr" => self.push_block(Block::SceneBreak),
+            "math" => self.math(id),
+            "svg" => self.svg(id),
+            "ruby" => self.ruby(id),
+            "img" | "image" => {
+                let doc = self.doc;
+                let src = doc
+                    .attr(id, "src")
+                    .or_else(|| doc.attr(id, "xlink:href"))
+                    .or_else(|| doc.attr(id, "href"));
+                if let Some(src) = src {
+                    let (mut path, _) = resolve_link(self.path, src);
+                    if self.options.images != Graphics::None && path.ends_with(".svg") {
+                        path = svg::rasterize_file(&path).unwrap_or(path);
                     }
-                    break;
+                    let alt = doc.attr(id, "alt").unwrap_or("").to_owned();
+                    self.push_block(Block::Picture(Picture { path, alt }));
+                }
+            }
+            "br" => self.line_break(),
+            // Verse of a FictionBook poem
+            "v" => {
+                for child in self.doc.children(id) {
+                    self.walk(*child);
+                }
+                self.line_break();
+            }
+            _ => {
+                for child in self.doc.children(id) {
+                    self.walk(*child);
+                }
+            }
+        }
+    }
+
+    /// Inline SVG drawn as an image when images are shown, otherwise what it says in words.
+    fn svg(&mut self, id: NodeId) {
+        let doc = self.doc;
+        let descendants = doc.descendants(id);
+        let has = |name: &str| descendants.iter().any(|node| doc.name(*node) == Some(name));
+        // Cover pages often only wrap a bitmap in an SVG
+        if has("image") && !has("text") {
+            for child in doc.children(id) {
+                self.walk(*child);
+            }
+            return;
+        }
+
+        let (label, texts) = svg::text(doc, id);
+        if self.options.images != Graphics::None {
+            if let Some(path) = svg::rasterize_element(doc, id, self.path) {
+                let alt = label.unwrap_or_default();
+                self.push_block(Block::Picture(Picture { path, alt }));
+                return;
+            }
+        }
+
+        let outer_alignment = self.alignment.replace(Alignment::Center);
+        if let Some(label) = label {
+            let mut style = self.style.clone();
+            style.keys.push("image_alt".to_owned());
+            self.current.push(&format!("[{}]", label), &style);
+            self.current.push("\n", &self.style);
+        }
+        for text in texts {
+            self.current.push(&text, &self.style);
+            self.current.push("\n", &self.style);
+        }
+        self.flush();
+        self.alignment = outer_alignment;
+    }
+
+    /// MathML formula as text, drawn over several lines when it's a block of its own and
+    /// the layout asks for it.
+    fn math(&mut self, id: NodeId) {
+        let doc = self.doc;
+        let mut style = self.style.clone();
+        style.keys.push("math".to_owned());
+        if doc.attr(id, "display") != Some("block") {
+            self.current.push(&math::linear(doc, id), &style);
+            return;
+        }
+
+        self.flush();
+        let lines = match self.options.math {
+            MathLayout::TwoDimensional => math::two_dimensional(doc, id),
+            MathLayout::Linear => vec![],
+        };
+        if lines.len() > 1 {
+            let lines = lines
+                .iter()
+                .map(|text| {
+                    let mut line = Line::new();
+                    line.push(text, &style);
+                    line
+                })
+                .collect();
+            self.push_block(Block::Preformatted(Preformatted {
+                lines,
+                alignment: Alignment::Center,
+            }));
+            return;
+        }
+        let outer_alignment = self.alignment.replace(Alignment::Center);
+        self.current.push(&math::linear(doc, id), &style);
+        self.flush();
+        self.alignment = outer_alignment;
+    }
+
+    /// Reference to a note drawn as a superscript marker linking to the note.
+    fn note_reference(&mut self, id: NodeId) {
+        let text = self.doc.text_content(id);
+        let label = text
+            .trim()
+            .trim_start_matches(['[', '('])
+            .trim_end_matches([']', ')']);
+        let marker: String = match label.chars().all(|c| superscript(c).is_some()) {
+            true => label.chars().filter_map(superscript).collect(),
+            false => format!("[{}]", label),
+        };
+        let mut style = self.style.clone();
+        style.keys.push("noteref".to_owned());
+        style.link = self.doc.attr(id, "href").map(str::to_owned);
+        self.current.push(&marker, &style);
+    }
+
+    fn table(&mut self, id: NodeId) -> Table {
+        let mut table = Table { rows: vec![] };
+        self.collect_rows(id, &mut table.rows);
+        table
+    }
+
+    fn collect_rows(&mut self, id: NodeId, rows: &mut Vec<Row>) {
+        let doc = self.doc;
+        for child in doc.children(id) {
+            match doc.name(*child) {
+                Some("thead") | Some("tbody") | Some("tfoot") => self.collect_rows(*child, rows),
+                Some("tr") => rows.push(self.row(*child)),
+                Some("caption") => {
+                    let outer_alignment = self.alignment.replace(Alignment::Center);
+                    self.walk(*child);
+                    self.flush();
+                    self.alignment = outer_alignment;
                 }
-                let regex = &regexes[total_matches[0]];
-                let tag = &tags[total_matches[0]];
+                _ => {}
+            }
+        }
+    }
 
-                let what_matched = Regex::new(&regex).unwrap().captures(&tag_content).unwrap();
+    fn row(&self, id: NodeId) -> Row {
+        let doc = self.doc;
+        let cells = doc
+            .children(id)
+            .iter()
+            .filter_map(|child| match doc.name(*child) {
+                Some("td") => Some(self.cell(*child, false)),
+                Some("th") => Some(self.cell(*child, true)),
+                _ => None,
+            })
+            .collect();
+        Row { cells }
+    }
 
-                if what_matched.len() > 2 {
-                    panic!("More matched!")
+    /// Lays out cell content on its own so it can be wrapped at the column width.
+    fn cell(&self, id: NodeId, header: bool) -> Cell {
+        let alignment = match header {
+            true => Alignment::Center,
+            false => Alignment::Left,
+        };
+        let key = match header {
+            true => Some("th"),
+            false => None,
+        };
+        let blocks = self.blocks_of(id, key, Some(alignment));
+
+        let colspan = self
+            .doc
+            .attr(id, "colspan")
+            .and_then(|colspan| colspan.trim().parse().ok())
+            .unwrap_or(1)
+            .clamp(1, 1000);
+        Cell {
+            blocks,
+            colspan,
+            header,
+        }
+    }
+
+    fn list(&self, id: NodeId) -> List {
+        let doc = self.doc;
+        let ordered = doc.name(id) == Some("ol");
+        let list_type = doc.attr(id, "type");
+        let items: Vec<NodeId> = doc
+            .children(id)
+            .iter()
+            .copied()
+            .filter(|child| doc.name(*child) == Some("li"))
+            .collect();
+
+        let reversed = doc.attr(id, "reversed").is_some();
+        let step = if reversed { -1 } else { 1 };
+        let mut value = doc
+            .attr(id, "start")
+            .and_then(|start| start.trim().parse().ok())
+            .unwrap_or(if reversed { items.len() as i64 } else { 1 });
+
+        let mut list = List {
+            items: vec![],
+            min_indent: 0,
+        };
+        for item in items {
+            if let Some(item_value) = doc.attr(item, "value").and_then(|v| v.trim().parse().ok()) {
+                value = item_value;
+            }
+            let marker = match ordered {
+                true => number(doc.attr(item, "type").or(list_type), value),
+                false => bullet(doc.attr(item, "type").or(list_type), self.list_depth).to_owned(),
+            };
+            value = value.saturating_add(step);
+
+            let mut renderer = self.nested(self.alignment);
+            renderer.list_depth += 1;
+            renderer.walk(item);
+            renderer.flush();
+            list.items.push(ListItem {
+                marker,
+                blocks: renderer.blocks,
+            });
+        }
+        list
+    }
+
+    /// Terms stay at the current indentation, their definitions are indented under them.
+    fn definitions(&mut self, id: NodeId) {
+        let doc = self.doc;
+        for child in doc.children(id) {
+            match doc.name(*child) {
+                Some("dd") => {
+                    let blocks = self.blocks_of(*child, None, self.alignment);
+                    self.push_block(Block::List(List {
+                        items: vec![ListItem {
+                            marker: String::new(),
+                            blocks,
+                        }],
+                        min_indent: 4,
+                    }));
                 }
+                // Terms and anything else, like the `div`s grouping them, flow as usual
+                _ => self.walk(*child),
+            }
+        }
+        self.flush();
+    }
 
-                let whole = what_matched.get(0).unwrap();
-                let inner = what_matched.get(1).unwrap().as_str();
-
-                let styled = &styler.style(inner, tag);
-
-                output.replace_range(whole.range(), styled);
-
-                tag_content = output.clone();
-                i += 1;
-            }
-
-            //TODO: change this to something better
-            let words = tag_content.split(" ").collect::<Vec<_>>();
-            let mut char_counter = 0;
-            let mut tmp_words: Vec<&str> = vec![];
-            for word in words {
-                match word {
-                    "NEW_LINE" => {
-                        if !tmp_words.is_empty() {
-                            if !extracted_lines.is_empty() {
-                                let mut last = extracted_lines.pop().unwrap();
-                                last.push_str(tmp_words.join(" ").as_str());
-                                extracted_lines.push(last);
-                                tmp_words.clear();
-                                char_counter = 0;
-                            }
-                        }
-                        extracted_lines.push("".to_owned());
-                        continue;
-                    }
-                    _ => {
-                        char_counter += word.chars().count();
-                        tmp_words.push(word);
-                        if char_counter >= max_chars_in_line as usize {
-                            extracted_lines.push(tmp_words.join(" "));
-                            tmp_words.clear();
-                            char_counter = 0;
-                        }
-                    }
+    /// Lines of a `pre` element exactly as written, highlighted when a `language-xxx`
+    /// class names a known language.
+    fn preformatted(&self, id: NodeId) -> Preformatted {
+        let doc = self.doc;
+        let mut style = self.style.clone();
+        style.keys.push("pre".to_owned());
+        let mut text = Line::new();
+        self.collect_verbatim(id, &mut style, &mut text);
+
+        let mut lines = vec![Line::new()];
+        for span in &text.spans {
+            for (i, piece) in span.text.replace('\t', "    ").split('\n').enumerate() {
+                if i > 0 {
+                    lines.push(Line::new());
+                }
+                lines.last_mut().unwrap().push(piece, &span.style);
+            }
+        }
+        // A newline right after the opening tag isn't part of the content
+        if lines.len() > 1 && lines[0].is_empty() {
+            lines.remove(0);
+        }
+        if lines.len() > 1 && lines.last().unwrap().is_empty() {
+            lines.pop();
+        }
+
+        let code = doc
+            .children(id)
+            .iter()
+            .find(|child| doc.name(**child) == Some("code"));
+        let language = doc
+            .attr(id, "class")
+            .and_then(language_from_class)
+            .or_else(|| {
+                code.and_then(|code| doc.attr(*code, "class"))
+                    .and_then(language_from_class)
+            });
+        if let Some(language) = language {
+            lines = highlight(lines, language);
+        }
+        Preformatted {
+            lines,
+            alignment: Alignment::Left,
+        }
+    }
+
+    fn collect_verbatim(&self, id: NodeId, style: &mut Style, out: &mut Line) {
+        let doc = self.doc;
+        if let Some(text) = doc.text(id) {
+            out.push(text, style);
+            return;
+        }
+        let name = doc.name(id).unwrap_or("");
+        if name == "br" {
+            out.push("\n", style);
+        }
+        let is_styled = STYLED_TAGS.contains(&name);
+        if is_styled {
+            style.keys.push(name.to_owned());
+        }
+        for child in doc.children(id) {
+            self.collect_verbatim(*child, style, out);
+        }
+        if is_styled {
+            style.keys.pop();
+        }
+    }
+
+    /// Ruby bases with the annotations following them, `rp` fallback parentheses are
+    /// left out as annotations get their own.
+    fn ruby(&mut self, id: NodeId) {
+        let doc = self.doc;
+        let mut bases = vec![];
+        for child in doc.children(id) {
+            match doc.name(*child) {
+                Some("rp") => {}
+                Some("rt") | Some("rtc") => {
+                    let annotation = annotation_text(doc, *child);
+                    self.ruby_base(
+                        &bases,
+                        annotation.split_whitespace().collect::<Vec<_>>().join(" "),
+                    );
+                    bases.clear();
                 }
+                None if doc.text(*child).is_some_and(|text| text.trim().is_empty()) => {}
+                _ => bases.push(*child),
             }
-            if char_counter != 0 {
-                extracted_lines.push(tmp_words.join(" "));
+        }
+        self.ruby_base(&bases, String::new());
+    }
+
+    fn ruby_base(&mut self, bases: &[NodeId], annotation: String) {
+        let doc = self.doc;
+        if annotation.is_empty() || self.options.ruby == RubyLayout::Hidden {
+            for base in bases {
+                self.walk(*base);
             }
+            return;
         }
-        extracted_lines
+        match self.options.ruby {
+            RubyLayout::Above => {
+                let base: String = bases.iter().map(|base| doc.text_content(*base)).collect();
+                let (left, right) = pad_base(width(base.trim()), &annotation);
+                // Padding would glue the base to its neighbours, lines can still break
+                // around it
+                self.current.push(ZERO_WIDTH_SPACE, &self.style);
+                let outer_ruby = self.style.ruby.replace(annotation);
+                self.current.push(&left, &self.style);
+                for base in bases {
+                    self.walk(*base);
+                }
+                self.current.push(&right, &self.style);
+                self.style.ruby = outer_ruby;
+                self.current.push(ZERO_WIDTH_SPACE, &self.style);
+            }
+            _ => {
+                for base in bases {
+                    self.walk(*base);
+                }
+                let mut style = self.style.clone();
+                style.keys.push("rt".to_owned());
+                self.current.push(&format!("({})", annotation), &style);
+            }
+        }
+    }
+
+    /// Renderer for content laid out on its own, like a table cell or a list item.
+    fn nested(&self, alignment: Option<Alignment>) -> TreeRenderer<'d> {
+        TreeRenderer {
+            doc: self.doc,
+            path: self.path,
+            options: self.options,
+            blocks: vec![],
+            current: Line::new(),
+            style: self.style.clone(),
+            alignment,
+            list_depth: self.list_depth,
+            after_text: false,
+            stylesheet: self.stylesheet,
+            indent: self.indent,
+            small_caps: self.small_caps,
+            anchors: self.anchors.clone(),
+            direction: self.direction,
+            verse: self.verse,
+        }
+    }
+
+    /// Blocks made from the element `id`, styled with `key` when given.
+    fn blocks_of(&self, id: NodeId, key: Option<&str>, alignment: Option<Alignment>) -> Vec<Block> {
+        let mut renderer = self.nested(alignment);
+        if let Some(key) = key {
+            renderer.style.keys.push(key.to_owned());
+        }
+        renderer.walk(id);
+        renderer.flush();
+        renderer.blocks
+    }
+
+    /// Appends text turning every run of whitespace into a single space. Only ASCII
+    /// whitespace collapses, like in HTML, so non-breaking spaces are kept.
+    fn push_collapsed(&mut self, text: &str) {
+        let mut collapsed = String::with_capacity(text.len());
+        let mut last_was_space = self
+            .current
+            .spans
+            .last()
+            .is_none_or(|span| span.text.ends_with([' ', '\n']));
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                if !last_was_space {
+                    collapsed.push(' ');
+                }
+                last_was_space = true;
+            } else {
+                collapsed.push(c);
+                last_was_space = false;
+            }
+        }
+        if self.small_caps {
+            collapsed = collapsed.to_uppercase();
+        }
+        if self.anchors.is_empty() || collapsed.trim().is_empty() {
+            self.current.push(&collapsed, &self.style);
+            return;
+        }
+        let mut style = self.style.clone();
+        style.anchors = std::mem::take(&mut self.anchors);
+        self.current.push(&collapsed, &style);
+    }
+
+    fn flush(&mut self) {
+        let mut paragraph = std::mem::take(&mut self.current);
+        trim_end(&mut paragraph);
+        if paragraph.is_empty() {
+            return;
+        }
+        let direction = self.direction.unwrap_or(self.options.direction);
+        let verse = self.verse || looks_like_verse(&paragraph);
+        let alignment = self.alignment.unwrap_or(self.options.alignment);
+        let is_text = !verse
+            && self.style.keys.iter().any(|key| key == "p")
+            && matches!(alignment, Alignment::Left | Alignment::Justify);
+        // Right-to-left text starts on the right unless the book says otherwise
+        let alignment = match (self.alignment, direction, alignment) {
+            (None, Direction::Rtl, Alignment::Left) => Alignment::Right,
+            _ => alignment,
+        };
+        let indent = match self.indent {
+            Some(indent) => indent,
+            None if self.after_text && is_text => self.options.first_line_indent,
+            None => 0,
+        };
+        self.push_block(Block::Paragraph(Paragraph {
+            line: paragraph,
+            alignment,
+            indent,
+            direction,
+            verse,
+        }));
+        self.after_text = is_text;
+    }
+
+    /// Forced line break, spaces before it are dropped.
+    fn line_break(&mut self) {
+        if let Some(last) = self.current.spans.last_mut() {
+            last.text.truncate(last.text.trim_end_matches(' ').len());
+        }
+        self.current.push("\n", &self.style);
+    }
+
+    fn push_block(&mut self, block: Block) {
+        self.blocks.push(block);
+        self.after_text = false;
     }
 }
 
+/// Text of a ruby annotation without the fallback parentheses of `rp` elements.
+fn annotation_text(doc: &Document, id: NodeId) -> String {
+    if let Some(text) = doc.text(id) {
+        return text.to_owned();
+    }
+    match doc.name(id) {
+        Some("rp") => String::new(),
+        _ => doc
+            .children(id)
+            .iter()
+            .map(|child| annotation_text(doc, *child))
+            .collect(),
+    }
+}
+
+/// Drops whitespace, forced line breaks included, from the end of a line.
+fn trim_end(line: &mut Line) {
+    while let Some(last) = line.spans.last_mut() {
+        last.text.truncate(
+            last.text
+                .trim_end_matches(|c: char| c.is_ascii_whitespace())
+                .len(),
+        );
+        if !last.text.is_empty() {
+            break;
+        }
+        line.spans.pop();
+    }
+}
+
+/// Whether the `epub:type` of an element lists `kind`.
+fn has_epub_type(doc: &Document, id: NodeId, kind: &str) -> bool {
+    doc.attr(id, "epub:type")
+        .is_some_and(|types| types.split_whitespace().any(|t| t == kind))
+}
+
+/// Poems and their stanzas, by their FictionBook names, classes or `epub:type`.
+fn is_verse(doc: &Document, id: NodeId) -> bool {
+    matches!(doc.name(id), Some("poem") | Some("stanza"))
+        || has_verse_markup(doc, id, &["poem", "poetry", "stanza", "verse"])
+}
+
+/// A few short lines broken with `br`, like a poem quoted without markup.
+fn looks_like_verse(paragraph: &Line) -> bool {
+    let text = paragraph.text();
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.len() >= VERSE_LINES && lines.iter().all(|line| width(line) <= VERSE_WIDTH)
+}
+
+/// Whether a class or an `epub:type`, with or without the `z3998:` prefix, is one of
+/// `kinds`.
+fn has_verse_markup(doc: &Document, id: NodeId, kinds: &[&str]) -> bool {
+    let classes = doc.attr(id, "class").unwrap_or("").split_whitespace();
+    let types = doc.attr(id, "epub:type").unwrap_or("").split_whitespace();
+    classes
+        .chain(types.map(|kind| kind.strip_prefix("z3998:").unwrap_or(kind)))
+        .any(|kind| kinds.contains(&kind.to_ascii_lowercase().as_str()))
+}
+
+/// Footnotes and endnotes, shown only in a popup when their reference is followed.
+fn is_note(doc: &Document, id: NodeId) -> bool {
+    let note_role = doc
+        .attr(id, "role")
+        .is_some_and(|role| role == "doc-footnote" || role == "doc-endnote");
+    doc.name(id) == Some("aside")
+        && (note_role
+            || ["footnote", "endnote", "rearnote", "note"]
+                .iter()
+                .any(|kind| has_epub_type(doc, id, kind)))
+}
+
+/// Element to show for a note target: the target itself when it's a block or a note,
+/// otherwise the closest block holding it, like the paragraph of a backlink.
+fn note_root(doc: &Document, fragment: &str) -> Option<NodeId> {
+    let target = doc
+        .descendants(doc.root())
+        .into_iter()
+        .find(|id| doc.attr(*id, "id") == Some(fragment))?;
+    let mut node = target;
+    loop {
+        let name = doc.name(node).unwrap_or("");
+        if name == "body" || name == "html" {
+            return Some(target);
+        }
+        if BLOCK_TAGS.contains(&name) || is_note(doc, node) {
+            return Some(node);
+        }
+        node = doc.parent(node)?;
+    }
+}
+
+fn superscript(c: char) -> Option<char> {
+    let superscript = match c {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '*' => '*',
+        '†' => '†',
+        '‡' => '‡',
+        _ => return None,
+    };
+    Some(superscript)
+}
+
+/// File and fragment a link from `filepath` points to, the file is the same one when
+/// the link only has a fragment.
+pub fn resolve_link(filepath: &str, href: &str) -> (String, Option<String>) {
+    let (file, fragment) = match href.split_once('#') {
+        Some((file, fragment)) => (file, Some(fragment.to_owned())),
+        None => (href, None),
+    };
+    if file.is_empty() {
+        return (filepath.to_owned(), fragment);
+    }
+    let directory = Path::new(filepath).parent().unwrap_or(Path::new(""));
+    (
+        directory.join(file).to_string_lossy().into_owned(),
+        fragment,
+    )
+}
+
+fn is_heading(name: &str) -> bool {
+    matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+/// Scheme of a link, like `https`, links inside the book have none.
+pub fn scheme(href: &str) -> Option<&str> {
+    let start = href.split(['/', '#', '?']).next().unwrap_or("");
+    start.split_once(':').map(|(scheme, _)| scheme)
+}
+
+/// Whether a link leaves the book for a web page or a mail. Links with other schemes,
+/// like `javascript:` or `file:`, are neither opened nor followed.
+pub fn is_external(href: &str) -> bool {
+    scheme(href).is_some_and(|scheme| {
+        ["http", "https", "mailto"]
+            .iter()
+            .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+    })
+}
+
+/// Index of the first line holding the element with the given id.
+pub fn anchor_line(lines: &[Line], id: &str) -> Option<usize> {
+    lines.iter().position(|line| {
+        line.spans
+            .iter()
+            .any(|span| span.style.anchors.iter().any(|anchor| anchor == id))
+    })
+}
+
 pub enum HtmlReadFrom {
     Line(usize),
     Marker(String),
 }
 
+#[cfg(test)]
 mod tests {
     #[test]
     fn parsing_html_works() {
-        use crate::html::HtmlToLine;
-        use crate::styler::EmptyStyler;
+        use crate::html::{HtmlToLine, LayoutOptions};
 
         let expected_lines = vec![
-            "ABC",
-            "D EF",
-            "PLACEK",
-            "",
-            "bleblebleblebleble",
-            "",
-            "AB",
-            "CD",
-            "ABCD",
-            "A B",
-            "C D",
-            "ABCD",
+            "ABC D", "EF", "PLACEK", "", "bleble", "bleble", "bleble", "AB CD", "ABCD", "A B C",
+            "D", "ABCD", "E F", "GH", "IJ", "KL", "MN", "O<P", "QR", "S", "T",
         ];
         let file_path = "./test_data/test_file.html";
-        let styler = EmptyStyler::new();
-        let lines = HtmlToLine::as_lines(file_path, &styler, 2);
+        let options = LayoutOptions::new(6);
+        let lines = HtmlToLine::as_lines(file_path, &options);
 
         assert_eq!(expected_lines.len(), lines.len());
 
         for (i, expected_line) in expected_lines.iter().enumerate() {
-            assert_eq!(expected_line, &lines[i]);
+            assert_eq!(expected_line, &lines[i].text());
         }
     }
+
+    #[test]
+    fn styles_are_kept_per_span() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+        use crate::line::Style;
+
+        let options = LayoutOptions::new(6);
+        let lines = HtmlToLine::as_lines("./test_data/test_file.html", &options);
+        let styled = lines.iter().find(|line| line.text() == "ABCD").unwrap();
+
+        assert_eq!(styled.spans.len(), 1);
+        assert_eq!(styled.spans[0].style, Style::new("h2"));
+
+        let nested = lines
+            .iter()
+            .filter(|line| line.text() == "ABCD")
+            .nth(1)
+            .unwrap();
+        assert_eq!(nested.spans[0].text, "AB");
+        assert_eq!(nested.spans[0].style, Style::new("p"));
+        assert_eq!(nested.spans[1].text, "CD");
+        assert_eq!(nested.spans[1].style.keys, vec!["p", "span"]);
+    }
+
+    #[test]
+    fn tables_are_drawn_as_boxes_or_records() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let mut options = LayoutOptions::new(30);
+        let lines: Vec<String> = HtmlToLine::as_lines("./test_data/table_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "            Prices",
+                "┌──────┬──────┐",
+                "│ Item │ Cost │",
+                "╞══════╪══════╡",
+                "│ Tea  │ 2    │",
+                "├──────┴──────┤",
+                "│ Sold out    │",
+                "└─────────────┘",
+            ]
+        );
+
+        options.max_chars_in_line = 10;
+        let lines: Vec<String> = HtmlToLine::as_lines("./test_data/table_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["  Prices", "Item: Tea", "Cost: 2", "", "Item: Sold", "out"]
+        );
+
+        // A spanning cell wider than the columns it covers want to be
+        options.max_chars_in_line = 30;
+        let lines: Vec<String> = HtmlToLine::as_lines("./test_data/table_span_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "┌───────────────────┬────────┐",
+                "│ a b c d e f g h i │ z      │",
+                "│ j k l m n o p     │        │",
+                "├───────────────────┴────────┤",
+                "│ abcdefghij                 │",
+                "└────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_have_markers_and_hanging_indent() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let options = LayoutOptions::new(10);
+        let lines: Vec<String> = HtmlToLine::as_lines("./test_data/list_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "• one two",
+                "  three",
+                "• four",
+                "  ◦ five",
+                "ix. nine",
+                " x. ten",
+                "2. b",
+                "1. a",
+                "term",
+                "    means",
+            ]
+        );
+
+        // Numbers stop at the ends of the range instead of overflowing
+        let options = LayoutOptions::new(30);
+        let lines: Vec<String> =
+            HtmlToLine::as_lines("./test_data/list_bounds_file.html", &options)
+                .iter()
+                .map(|line| line.text())
+                .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "9223372036854775807. a",
+                "9223372036854775807. b",
+                "-9223372036854775808. c",
+                "-9223372036854775808. d",
+            ]
+        );
+    }
+
+    #[test]
+    fn preformatted_text_is_kept_verbatim() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+        use crate::layout::Overflow;
+
+        let mut options = LayoutOptions::new(20);
+        let lines = HtmlToLine::as_lines("./test_data/pre_file.html", &options);
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Run cargo build",
+                "first.",
+                "fn main() {",
+                "    let x = 1; // a↩",
+                " long comment",
+                "}",
+            ]
+        );
+        assert_eq!(lines[0].spans[1].style.keys.last().unwrap(), "code");
+        assert_eq!(lines[2].spans[0].style.keys.last().unwrap(), "code_keyword");
+
+        options.pre_overflow = Overflow::Truncate;
+        let lines = HtmlToLine::as_lines("./test_data/pre_file.html", &options);
+        assert_eq!(lines[3].text(), "    let x = 1; // a→");
+        assert_eq!(lines.len(), 5);
+
+        // Graphemes wider than what's left of a narrow cell still wrap
+        options.pre_overflow = Overflow::Wrap;
+        options.max_chars_in_line = 23;
+        let lines = HtmlToLine::as_lines("./test_data/pre_cell_file.html", &options);
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "┌────┬────────────────┐",
+                "│ 日 │ aaaaaaaaaaaaaa │",
+                "│ 本 │ bbbbbbbbbbbbbb │",
+                "│ 語 │                │",
+                "│ 日 │                │",
+                "│ 本 │                │",
+                "│ 語 │                │",
+                "└────┴────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn entities_are_decoded_and_whitespace_collapsed() {
+        use crate::dom::Document;
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let options = LayoutOptions::new(40);
+        let texts: Vec<String> = HtmlToLine::as_lines("./test_data/entities_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(texts, vec!["Tom & Jerry ——— <ok>", "x\u{a0}y z"]);
+
+        let document = Document::from_file("./test_data/entities_file.html").unwrap();
+        let titles: Vec<&str> = document
+            .descendants(document.root())
+            .into_iter()
+            .filter_map(|id| document.attr(id, "title"))
+            .collect();
+        assert_eq!(titles, vec!["a & b"]);
+    }
+
+    #[test]
+    fn blocks_are_spaced_indented_and_separated() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let mut options = LayoutOptions::new(20);
+        options.paragraph_spacing = 1;
+        let lines: Vec<String> = HtmlToLine::as_lines("./test_data/blocks_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Title",
+                "",
+                "One two",
+                "",
+                "Three",
+                "four",
+                "",
+                "       * * *",
+                "",
+                "    Quoted text",
+                "",
+                "    More",
+                "",
+                "Five",
+            ]
+        );
+
+        options.paragraph_spacing = 0;
+        options.first_line_indent = 2;
+        let lines: Vec<String> = HtmlToLine::as_lines("./test_data/blocks_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Title",
+                "One two",
+                "  Three",
+                "four",
+                "       * * *",
+                "    Quoted text",
+                "      More",
+                "Five",
+            ]
+        );
+    }
+
+    #[test]
+    fn book_css_is_applied() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let options = LayoutOptions::new(20);
+        let lines = HtmlToLine::as_lines("./test_data/css_file.html", &options);
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "One TWO",
+                "",
+                "                Next",
+                "    Side note",
+                "  Lorem ipsum"
+            ]
+        );
+
+        let style_of = |line: usize, text: &str| {
+            let span = lines[line]
+                .spans
+                .iter()
+                .find(|span| span.text.contains(text));
+            span.unwrap().style.keys.clone()
+        };
+        assert_eq!(style_of(3, "note"), vec!["div", "p", "bold"]);
+        assert_eq!(style_of(4, "ipsum"), vec!["p", "span", "italic"]);
+    }
+
+    #[test]
+    fn notes_are_hidden_and_rendered_on_their_own() {
+        use crate::html::{resolve_link, HtmlToLine, LayoutOptions};
+
+        let options = LayoutOptions::new(40);
+        let lines = HtmlToLine::as_lines("./test_data/notes_file.html", &options);
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(texts, vec!["Text¹ and more[b]."]);
+        let links: Vec<&str> = lines[0]
+            .spans
+            .iter()
+            .filter_map(|span| span.style.link.as_deref())
+            .collect();
+        assert_eq!(links, vec!["#n1", "notes_end.html#e2"]);
+
+        let (file, fragment) = resolve_link("./test_data/notes_file.html", links[0]);
+        let note = HtmlToLine::note_as_lines(&file, &fragment.unwrap(), &options);
+        assert_eq!(note[0].text(), "First note.");
+
+        let (file, fragment) = resolve_link("./test_data/notes_file.html", links[1]);
+        assert_eq!(file, "./test_data/notes_end.html");
+        let note = HtmlToLine::note_as_lines(&file, &fragment.unwrap(), &options);
+        assert_eq!(note[0].text(), "b. Second note.");
+    }
+
+    #[test]
+    fn right_to_left_paragraphs_are_reordered_and_right_aligned() {
+        use crate::bidi::Direction;
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let mut options = LayoutOptions::new(12);
+        options.paragraph_spacing = 0;
+        options.bidi = true;
+        let texts = |options: &LayoutOptions| -> Vec<String> {
+            HtmlToLine::as_lines("./test_data/bidi_file.html", options)
+                .iter()
+                .map(|line| line.text())
+                .collect()
+        };
+        assert_eq!(
+            texts(&options),
+            vec!["  world םולש", "Say םולש.", "        םלוע"]
+        );
+
+        options.direction = Direction::Rtl;
+        options.bidi = false;
+        assert_eq!(
+            texts(&options),
+            vec!["  שלום world", "   Say שלום.", "        עולם"]
+        );
+    }
+
+    #[test]
+    fn verses_are_kept_with_hanging_indent_and_stanzas_apart() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let mut options = LayoutOptions::new(24);
+        options.first_line_indent = 2;
+        let lines: Vec<String> = HtmlToLine::as_lines("./test_data/verse_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Litwo! Ojczyzno moja! ty",
+                "    jesteś jak zdrowie:",
+                "\u{A0}\u{A0}\u{A0}\u{A0}Ile cię trzeba cenić",
+                "",
+                "Dziś piękność twą",
+                "",
+                // A few short lines broken with `br` are verses too
+                "Roses are red,",
+                "violets are blue and so",
+                "    is the sea,",
+                "sugar is sweet.",
+                // but not when one of them is as long as prose
+                "Dear Sir,",
+                "thank you for your",
+                "letter of the fifth,",
+                "which reached me only",
+                "yesterday.",
+                "Yours.",
+                // and a line outside of a poem is a paragraph
+                "  Not in a poem, just a",
+                "paragraph",
+                "First verse",
+                "second verse",
+                "",
+                "Last",
+            ]
+        );
+    }
+
+    #[test]
+    fn classes_of_the_theme_are_style_keys() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let classes = ["stanza".to_owned()];
+        let mut options = LayoutOptions::new(40);
+        options.classes = &classes;
+        let lines = HtmlToLine::as_lines("./test_data/verse_file.html", &options);
+        assert_eq!(
+            lines[0].spans[0].style.keys,
+            vec!["div", "div", ".stanza", "p"]
+        );
+        let roses = lines.iter().find(|line| line.text() == "Roses are red,");
+        assert_eq!(roses.unwrap().spans[0].style.keys, vec!["p"]);
+    }
+
+    #[test]
+    fn ruby_is_inline_above_or_hidden() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+        use crate::ruby::RubyLayout;
+
+        let mut options = LayoutOptions::new(12);
+        let texts = |options: &LayoutOptions| -> Vec<String> {
+            HtmlToLine::as_lines("./test_data/ruby_file.html", options)
+                .iter()
+                .map(|line| line.text())
+                .collect()
+        };
+        assert_eq!(
+            texts(&options),
+            vec!["私は東京(と", "うきょう)へ", "行(い)く。"]
+        );
+
+        options.ruby = RubyLayout::Hidden;
+        assert_eq!(texts(&options), vec!["私は東京へ行", "く。"]);
+
+        // The padded base is kept whole, the annotation over it
+        options.ruby = RubyLayout::Above;
+        assert_eq!(texts(&options)[..2], ["私は", "とうきょう"]);
+        options.max_chars_in_line = 14;
+        assert_eq!(
+            texts(&options),
+            vec![
+                "    とうきょう",
+                "私は\u{A0}\u{A0}\u{A0}東京\u{A0}\u{A0}\u{A0}",
+                "  い",
+                "へ行く。"
+            ]
+        );
+    }
+
+    #[test]
+    fn math_is_linear_inline_and_drawn_in_blocks() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+        use crate::math::MathLayout;
+
+        let mut options = LayoutOptions::new(11);
+        options.paragraph_spacing = 0;
+        let texts = |options: &LayoutOptions| -> Vec<String> {
+            HtmlToLine::as_lines("./test_data/math_file.html", options)
+                .iter()
+                .map(|line| line.text())
+                .collect()
+        };
+        assert_eq!(texts(&options), vec!["Area is", "πr^2 here.", "    1/n"]);
+
+        options.math = MathLayout::TwoDimensional;
+        assert_eq!(
+            texts(&options),
+            vec!["Area is", "πr^2 here.", "     1", "     ─", "     n"]
+        );
+    }
+
+    #[test]
+    fn svg_text_is_shown_without_images() {
+        use crate::html::{HtmlToLine, LayoutOptions};
+
+        let mut options = LayoutOptions::new(11);
+        options.paragraph_spacing = 0;
+        let texts: Vec<String> = HtmlToLine::as_lines("./test_data/svg_file.html", &options)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(
+            texts,
+            vec!["Before", "  [Chart]", "    Top", "  Bottom", "After"]
+        );
+    }
+
+    #[test]
+    fn links_and_anchors_are_kept_in_spans() {
+        use crate::html::{anchor_line, HtmlToLine, LayoutOptions};
+
+        let options = LayoutOptions::new(20);
+        let lines = HtmlToLine::as_lines("./test_data/links_file.html", &options);
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(
+            texts,
+            vec!["See the later part", "or the web.", "Later part"]
+        );
+
+        let links: Vec<(&str, &str)> = lines[..2]
+            .iter()
+            .flat_map(|line| &line.spans)
+            .filter_map(|span| Some((span.text.as_str(), span.style.link.as_deref()?)))
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                ("the ", "#later"),
+                ("later", "#later"),
+                (" part", "#later"),
+                ("the web", "https://example.com"),
+            ]
+        );
+
+        assert_eq!(anchor_line(&lines, "later"), Some(2));
+        assert_eq!(anchor_line(&lines, "inner"), Some(2));
+        assert_eq!(anchor_line(&lines, "missing"), None);
+    }
 }
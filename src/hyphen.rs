@@ -0,0 +1,135 @@
+use hypher::Lang;
+
+pub struct Hyphenation {
+    pub enabled: bool,
+    /// Words with fewer chars are never hyphenated.
+    pub min_word_length: usize,
+    /// Cells which must be gained on a line before a word gets hyphenated.
+    pub penalty: usize,
+    /// Added to `penalty` when the previous line already ends with a hyphen.
+    pub consecutive_penalty: usize,
+}
+
+impl Hyphenation {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            min_word_length: 6,
+            penalty: 3,
+            consecutive_penalty: 4,
+        }
+    }
+}
+
+/// ISO 639-2 codes, bibliographic and terminological, of languages with hyphenation
+/// patterns and their ISO 639-1 codes.
+const THREE_LETTER_CODES: [(&str, &str); 45] = [
+    ("afr", "af"),
+    ("alb", "sq"),
+    ("sqi", "sq"),
+    ("bel", "be"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("hrv", "hr"),
+    ("ces", "cs"),
+    ("cze", "cs"),
+    ("dan", "da"),
+    ("dut", "nl"),
+    ("nld", "nl"),
+    ("eng", "en"),
+    ("est", "et"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("fre", "fr"),
+    ("geo", "ka"),
+    ("kat", "ka"),
+    ("deu", "de"),
+    ("ger", "de"),
+    ("ell", "el"),
+    ("gre", "el"),
+    ("hun", "hu"),
+    ("ice", "is"),
+    ("isl", "is"),
+    ("ita", "it"),
+    ("kur", "ku"),
+    ("lat", "la"),
+    ("lit", "lt"),
+    ("mon", "mn"),
+    ("nob", "nb"),
+    ("nno", "nn"),
+    ("nor", "no"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("rus", "ru"),
+    ("srp", "sr"),
+    ("slk", "sk"),
+    ("slo", "sk"),
+    ("slv", "sl"),
+    ("spa", "es"),
+    ("swe", "sv"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+];
+
+/// Maps a language tag such as `pl`, `en-GB` or `pol` to hyphenation patterns.
+pub fn lang_from_tag(tag: &str) -> Option<Lang> {
+    let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+    let code = match primary.len() {
+        2 => primary.as_str(),
+        3 => THREE_LETTER_CODES
+            .iter()
+            .find(|(three, _)| *three == primary)
+            .map(|(_, two)| *two)?,
+        _ => return None,
+    };
+    let code = code.as_bytes();
+    Lang::from_iso([code[0], code[1]])
+}
+
+/// Byte offsets inside `word` where it may be hyphenated. Leading and trailing
+/// punctuation is never split off.
+pub fn break_points(word: &str, lang: Lang) -> Vec<usize> {
+    let core_start = word
+        .char_indices()
+        .find(|(_, c)| c.is_alphabetic())
+        .map_or(word.len(), |(i, _)| i);
+    let core_end = word
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_alphabetic())
+        .map_or(core_start, |(i, c)| i + c.len_utf8());
+    if core_start >= core_end {
+        return vec![];
+    }
+    let core = &word[core_start..core_end];
+    if !core.chars().all(char::is_alphabetic) {
+        return vec![];
+    }
+
+    let mut points = vec![];
+    let mut offset = core_start;
+    let mut syllables = hypher::hyphenate(core, lang).peekable();
+    while let Some(syllable) = syllables.next() {
+        offset += syllable.len();
+        if syllables.peek().is_some() {
+            points.push(offset);
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hyphen::lang_from_tag;
+    use hypher::Lang;
+
+    #[test]
+    fn primary_subtag_picks_the_language() {
+        assert_eq!(lang_from_tag("pl"), Some(Lang::Polish));
+        assert_eq!(lang_from_tag("en_GB"), Some(Lang::English));
+        assert_eq!(lang_from_tag("pol"), Some(Lang::Polish));
+        assert_eq!(lang_from_tag("ger"), Some(Lang::German));
+        assert_eq!(lang_from_tag("fil"), None);
+        assert_eq!(lang_from_tag("x"), None);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::align::{align, Alignment};
+use crate::bidi::{reorder, Direction};
+use crate::html::LayoutOptions;
+use crate::line::{Line, Style};
+use crate::list::List;
+use crate::picture::Picture;
+use crate::ruby::annotations;
+use crate::table::Table;
+use crate::wrap::{split_at_width, width, wrap_indented};
+
+/// Content of a document once inline elements are joined, ready to be laid out at
+/// any width.
+pub enum Block {
+    Paragraph(Paragraph),
+    Table(Table),
+    List(List),
+    Preformatted(Preformatted),
+    /// Separator between scenes, drawn as a centered marker.
+    SceneBreak,
+    /// Start of a new page in print, like before a chapter heading.
+    PageBreak,
+    Indented(Indented),
+    Picture(Picture),
+}
+
+/// What to do with preformatted lines wider than the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Cut the line and mark that something was hidden.
+    Truncate,
+    /// Continue on the next row, marking every row which continues.
+    Wrap,
+}
+
+/// Text kept verbatim, like `pre` blocks of code.
+pub struct Preformatted {
+    pub lines: Vec<Line>,
+    /// Where lines narrower than the text are put.
+    pub alignment: Alignment,
+}
+
+impl Preformatted {
+    fn layout(&self, max_width: usize, options: &LayoutOptions, out: &mut Vec<Line>) {
+        let marker = Style::new("pre_overflow");
+        let max_width = max_width.max(2);
+        for line in &self.lines {
+            if width(&line.text()) <= max_width {
+                out.push(align(line.clone(), self.alignment, max_width, true));
+                continue;
+            }
+            match options.pre_overflow {
+                Overflow::Truncate => {
+                    let (mut head, _) = split_at_width(line, max_width - 1);
+                    if width(&head.text()) < max_width {
+                        head.push("→", &marker);
+                    }
+                    out.push(head);
+                }
+                Overflow::Wrap => {
+                    let mut rest = line.clone();
+                    while width(&rest.text()) > max_width {
+                        let (mut head, tail) = split_at_width(&rest, max_width - 1);
+                        // A wide grapheme filling the column leaves no room for the marker
+                        if width(&head.text()) < max_width {
+                            head.push("↩", &marker);
+                        }
+                        out.push(head);
+                        rest = tail;
+                    }
+                    out.push(rest);
+                }
+            }
+        }
+    }
+}
+
+/// Inline content of one block element, forced line breaks are kept as `\n`.
+pub struct Paragraph {
+    pub line: Line,
+    pub alignment: Alignment,
+    /// Cells left empty at the start of the first line, on the right in right-to-left
+    /// paragraphs.
+    pub indent: usize,
+    pub direction: Direction,
+    /// Lines of a poem, each forced line break ends a verse which continues under a
+    /// hanging indent when it's too long.
+    pub verse: bool,
+}
+
+impl Paragraph {
+    /// Pieces of the paragraph between forced line breaks.
+    fn lines(&self) -> Vec<Line> {
+        let mut lines = vec![Line::new()];
+        for span in &self.line.spans {
+            for (i, piece) in span.text.split('\n').enumerate() {
+                if i > 0 {
+                    lines.push(Line::new());
+                }
+                lines.last_mut().unwrap().push(piece, &span.style);
+            }
+        }
+        lines
+    }
+}
+
+/// Indentation of the continuation of a verse too long for the screen.
+const HANGING_INDENT: usize = 4;
+
+/// Blocks shifted right, like the content of a `blockquote`.
+pub struct Indented {
+    pub indent: usize,
+    pub blocks: Vec<Block>,
+}
+
+impl Indented {
+    fn layout(
+        &self,
+        max_width: usize,
+        spacing: usize,
+        options: &LayoutOptions,
+        out: &mut Vec<Line>,
+    ) {
+        let indent = self.indent.min(max_width / 2);
+        let mut lines = vec![];
+        layout(
+            &self.blocks,
+            max_width - indent,
+            spacing,
+            options,
+            &mut lines,
+        );
+        for content in lines {
+            let mut line = Line::new();
+            if !content.is_empty() {
+                line.push(&" ".repeat(indent), &Style::default());
+                line.spans.extend(content.spans);
+            }
+            out.push(line);
+        }
+    }
+}
+
+impl Block {
+    /// Narrowest width the block can be laid out at without cutting words.
+    pub fn min_width(&self) -> usize {
+        match self {
+            Block::Paragraph(paragraph) => split_words(&paragraph.line)
+                .iter()
+                .map(|word| width(&word.text()))
+                .max()
+                .unwrap_or(0),
+            Block::Table(table) => table.min_width(),
+            Block::List(list) => list.min_width(),
+            // Too long lines are cut or wrapped, only a wide grapheme has to fit
+            Block::Preformatted(_) => 2,
+            Block::SceneBreak => 1,
+            Block::PageBreak => 0,
+            Block::Picture(_) => 1,
+            Block::Indented(indented) => {
+                indented.indent + blocks_width(&indented.blocks, Block::min_width)
+            }
+        }
+    }
+
+    /// Width the block takes when nothing has to be wrapped.
+    pub fn max_width(&self) -> usize {
+        match self {
+            Block::Paragraph(paragraph) => {
+                let widest = paragraph
+                    .lines()
+                    .iter()
+                    .map(|line| width(&line.text()))
+                    .max()
+                    .unwrap_or(0);
+                paragraph.indent + widest
+            }
+            Block::Table(table) => table.max_width(),
+            Block::List(list) => list.max_width(),
+            Block::Preformatted(preformatted) => preformatted
+                .lines
+                .iter()
+                .map(|line| width(&line.text()))
+                .max()
+                .unwrap_or(0),
+            Block::SceneBreak => 1,
+            Block::PageBreak => 0,
+            Block::Picture(picture) => picture.natural_width(),
+            Block::Indented(indented) => {
+                indented.indent + blocks_width(&indented.blocks, Block::max_width)
+            }
+        }
+    }
+}
+
+pub fn blocks_width(blocks: &[Block], measure: fn(&Block) -> usize) -> usize {
+    blocks.iter().map(measure).max().unwrap_or(0)
+}
+
+/// Lays blocks out one under another with `spacing` empty lines between them.
+pub fn layout(
+    blocks: &[Block],
+    max_width: usize,
+    spacing: usize,
+    options: &LayoutOptions,
+    out: &mut Vec<Line>,
+) {
+    let mut first = true;
+    let mut after_verse = false;
+    for block in blocks {
+        let mut lines = vec![];
+        match block {
+            Block::Paragraph(paragraph) => {
+                wrap_paragraph(paragraph, max_width, options, &mut lines)
+            }
+            Block::Table(table) => table.layout(max_width, options, &mut lines),
+            Block::List(list) => list.layout(max_width, options, &mut lines),
+            Block::Preformatted(preformatted) => {
+                preformatted.layout(max_width, options, &mut lines)
+            }
+            Block::SceneBreak => {
+                let mut marker = Line::new();
+                marker.push(options.scene_break, &Style::new("hr"));
+                lines.push(align(marker, Alignment::Center, max_width, true));
+            }
+            // Pages don't exist on a terminal, the break is just a wider gap
+            Block::PageBreak if !first => lines.push(Line::new()),
+            Block::PageBreak => {}
+            Block::Picture(picture) => picture.layout(max_width, options, &mut lines),
+            Block::Indented(indented) => indented.layout(max_width, spacing, options, &mut lines),
+        }
+        if lines.is_empty() {
+            continue;
+        }
+        // Stanzas stay apart even without spacing between paragraphs
+        let verse = matches!(block, Block::Paragraph(paragraph) if paragraph.verse);
+        let spacing = match after_verse && verse {
+            true => spacing.max(1),
+            false => spacing,
+        };
+        if !first {
+            out.extend(std::iter::repeat_n(Line::new(), spacing));
+        }
+        first = false;
+        after_verse = verse;
+        out.extend(lines);
+    }
+}
+
+/// Splits a paragraph on spaces keeping the style of every piece of each word.
+pub fn split_words(paragraph: &Line) -> Vec<Line> {
+    let mut words = vec![Line::new()];
+    for span in &paragraph.spans {
+        for (i, piece) in span.text.split([' ', '\n']).enumerate() {
+            if i > 0 {
+                words.push(Line::new());
+            }
+            words.last_mut().unwrap().push(piece, &span.style);
+        }
+    }
+    words
+}
+
+/// Wraps and aligns a paragraph, every forced line break starts a new line which is
+/// aligned like the last line of a paragraph.
+fn wrap_paragraph(
+    paragraph: &Paragraph,
+    max_width: usize,
+    options: &LayoutOptions,
+    out: &mut Vec<Line>,
+) {
+    let hanging = match paragraph.verse {
+        true => HANGING_INDENT.min(max_width / 2),
+        false => 0,
+    };
+    for (i, text) in paragraph.lines().iter().enumerate() {
+        let first_indent = match i {
+            0 => paragraph.indent.min(max_width / 2),
+            _ => 0,
+        };
+        let lines = wrap_indented(text, max_width, first_indent, hanging, options.hyphenation);
+        if lines.is_empty() {
+            out.push(Line::new());
+        }
+        let count = lines.len();
+        let rtl = paragraph.direction == Direction::Rtl;
+        for (j, line) in lines.into_iter().enumerate() {
+            let last = j + 1 == count;
+            let line = match options.bidi {
+                true => reorder(&line, paragraph.direction),
+                false => line,
+            };
+            // The last line of a justified right-to-left paragraph ends on the right
+            let alignment = match paragraph.alignment {
+                Alignment::Justify if rtl && last => Alignment::Right,
+                // Verses aren't stretched
+                Alignment::Justify if paragraph.verse => Alignment::Left,
+                alignment => alignment,
+            };
+            let indent = match j {
+                0 => first_indent,
+                _ => hanging,
+            };
+            let line = if indent > 0 && rtl {
+                align(line, alignment, max_width - indent, last)
+            } else if indent > 0 {
+                let mut indented = Line::new();
+                indented.push(&" ".repeat(indent), &Style::default());
+                let line = align(line, alignment, max_width - indent, last);
+                indented.spans.extend(line.spans);
+                indented
+            } else {
+                align(line, alignment, max_width, last)
+            };
+            out.extend(annotations(&line));
+            out.push(line);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::wrap::width;
+
+/// Semantic style of a piece of text. Keys are names of styled elements enclosing the
+/// text, outermost first, and are turned into colours by a `Styler` only when drawn.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+    pub keys: Vec<String>,
+    /// Language tag of the text, used to pick hyphenation patterns.
+    pub lang: Option<String>,
+    /// Target of the link the text is part of.
+    pub link: Option<String>,
+    /// Ids of the elements starting with this text, targets of links.
+    pub anchors: Vec<String>,
+    /// Annotation drawn above the text, which is the base of a ruby.
+    pub ruby: Option<String>,
+}
+
+impl Style {
+    pub fn new(key: &str) -> Self {
+        Self {
+            keys: vec![key.to_owned()],
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Span {
+    pub fn new(text: &str, style: Style) -> Self {
+        Self {
+            text: text.to_owned(),
+            style,
+        }
+    }
+}
+
+/// Rows of an image the terminal draws itself over the lines reserved for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRow {
+    /// Path of the image file.
+    pub path: String,
+    /// Cell the image starts at.
+    pub column: usize,
+    pub columns: usize,
+    /// Which of the image's `rows` this line is.
+    pub row: usize,
+    pub rows: usize,
+}
+
+/// One row of laid out text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Line {
+    pub spans: Vec<Span>,
+    pub image: Option<ImageRow>,
+}
+
+impl Line {
+    pub fn new() -> Self {
+        Self {
+            spans: vec![],
+            image: None,
+        }
+    }
+
+    /// Appends text, extending the last span when it has the same style.
+    pub fn push(&mut self, text: &str, style: &Style) {
+        if text.is_empty() {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(last) if &last.style == style => last.text.push_str(text),
+            _ => self.spans.push(Span::new(text, style.clone())),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.iter().all(|span| span.text.is_empty())
+    }
+
+    /// Span drawn at the given cell of the line.
+    pub fn span_at(&self, column: usize) -> Option<&Span> {
+        let mut start = 0;
+        self.spans.iter().find(|span| {
+            start += width(&span.text);
+            column < start
+        })
+    }
+}
+
+impl From<&str> for Line {
+    fn from(text: &str) -> Self {
+        let mut line = Line::new();
+        line.push(text, &Style::default());
+        line
+    }
+}
@@ -0,0 +1,143 @@
+use crate::html::LayoutOptions;
+use crate::layout::{layout, Block};
+use crate::line::{Line, Style};
+use crate::wrap::width;
+
+pub struct ListItem {
+    pub marker: String,
+    pub blocks: Vec<Block>,
+}
+
+/// Items drawn with their marker on the first line and wrapped lines hanging under
+/// the text instead of the marker.
+pub struct List {
+    pub items: Vec<ListItem>,
+    /// Smallest indentation, used by definition lists whose items have no markers.
+    pub min_indent: usize,
+}
+
+impl List {
+    fn indent(&self) -> usize {
+        let marker_width = self
+            .items
+            .iter()
+            .map(|item| width(&item.marker))
+            .max()
+            .unwrap_or(0);
+        self.min_indent.max(marker_width + 1)
+    }
+
+    pub fn min_width(&self) -> usize {
+        self.indent() + self.measure(Block::min_width)
+    }
+
+    pub fn max_width(&self) -> usize {
+        self.indent() + self.measure(Block::max_width)
+    }
+
+    fn measure(&self, measure: fn(&Block) -> usize) -> usize {
+        self.items
+            .iter()
+            .flat_map(|item| item.blocks.iter().map(measure))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn layout(&self, max_width: usize, options: &LayoutOptions, out: &mut Vec<Line>) {
+        let indent = self.indent().min(max_width / 2);
+        let marker_style = Style::new("list_marker");
+        for item in &self.items {
+            let mut lines = vec![];
+            layout(&item.blocks, max_width - indent, 0, options, &mut lines);
+            if lines.is_empty() {
+                lines.push(Line::new());
+            }
+            for (i, content) in lines.into_iter().enumerate() {
+                let mut line = Line::new();
+                match i {
+                    0 => {
+                        let padding = indent.saturating_sub(width(&item.marker) + 1);
+                        line.push(&" ".repeat(padding), &Style::default());
+                        line.push(&item.marker, &marker_style);
+                        line.push(" ", &Style::default());
+                    }
+                    _ => line.push(&" ".repeat(indent), &Style::default()),
+                }
+                line.spans.extend(content.spans);
+                out.push(line);
+            }
+        }
+    }
+}
+
+/// Bullet for an unordered list, by its `type` attribute or nesting depth.
+pub fn bullet(list_type: Option<&str>, depth: usize) -> &'static str {
+    match list_type.map(str::to_ascii_lowercase).as_deref() {
+        Some("disc") => "•",
+        Some("circle") => "◦",
+        Some("square") => "▪",
+        Some("none") => "",
+        _ => ["•", "◦", "▪"][depth % 3],
+    }
+}
+
+/// Marker of an ordered list item, `list_type` is the HTML `type` attribute.
+pub fn number(list_type: Option<&str>, value: i64) -> String {
+    let formatted = match list_type {
+        Some("a") if value > 0 => alphabetic(value as u64).to_lowercase(),
+        Some("A") if value > 0 => alphabetic(value as u64),
+        Some("i") if value > 0 && value < 4000 => roman(value as u64).to_lowercase(),
+        Some("I") if value > 0 && value < 4000 => roman(value as u64),
+        _ => value.to_string(),
+    };
+    format!("{}.", formatted)
+}
+
+fn alphabetic(mut value: u64) -> String {
+    let mut letters = vec![];
+    while value > 0 {
+        value -= 1;
+        letters.push((b'A' + (value % 26) as u8) as char);
+        value /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+fn roman(mut value: u64) -> String {
+    const NUMERALS: [(u64, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut out = String::new();
+    for (amount, numeral) in NUMERALS {
+        while value >= amount {
+            out.push_str(numeral);
+            value -= amount;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::list::number;
+
+    #[test]
+    fn numbers_follow_list_type() {
+        assert_eq!(number(None, 3), "3.");
+        assert_eq!(number(Some("a"), 28), "ab.");
+        assert_eq!(number(Some("I"), 14), "XIV.");
+        assert_eq!(number(Some("i"), 0), "0.");
+    }
+}
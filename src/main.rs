@@ -1,29 +1,64 @@
 #![allow(dead_code)]
 use std::env;
 
+mod align;
+mod bidi;
 mod config;
+mod cover;
+mod css;
+mod dom;
+mod graphics;
+mod highlight;
+mod hints;
 mod html;
+mod hyphen;
+mod layout;
+mod line;
+mod list;
+mod math;
 mod misc;
+mod picture;
 mod reader;
+mod ruby;
 mod styler;
+mod svg;
+mod table;
 mod term;
+mod viewer;
+mod wrap;
 
+use config::ReaderConfig;
 use reader::EpubReader;
 use std::error::Error;
+use std::path::PathBuf;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
+    let mut args = env::args().skip(1);
+    let mut file_path = None;
+    let mut theme = None;
+    let mut theme_file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--theme" => theme = args.next(),
+            "--theme-file" => theme_file = args.next(),
+            _ => file_path = Some(arg),
+        }
+    }
 
-    if args.len() < 2 {
+    let Some(file_path) = file_path else {
         println!("No file path provided");
         std::process::exit(0);
-    }
-
-    let file_path: &String = &args[1];
+    };
+    let theme_file = theme_file.or_else(default_theme_file);
 
-    let mut papcio = EpubReader::new();
+    let mut config = ReaderConfig::new(30, 5, "./tmp");
+    if let Some(theme) = &theme {
+        config.theme = theme;
+    }
+    config.theme_file = theme_file.as_deref();
+    let mut papcio = EpubReader::new(config);
 
-    match papcio.run(file_path) {
+    match papcio.run(&file_path) {
         Ok(()) => {}
         Err(msg) => {
             println!("{}", msg);
@@ -33,3 +68,12 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// `papcio/themes` in the user's config directory, when it's there.
+fn default_theme_file() -> Option<String> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    let path = config_dir.join("papcio").join("themes");
+    path.is_file().then(|| path.to_string_lossy().into_owned())
+}
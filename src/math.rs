@@ -0,0 +1,496 @@
+use crate::dom::{Document, NodeId};
+use crate::wrap::width;
+
+/// How MathML formulas are turned into text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathLayout {
+    /// On one line, like `x^2 + sqrt(y)/2`.
+    Linear,
+    /// Fractions, roots, scripts and matrices drawn over several lines when the formula
+    /// is a block of its own. Formulas inside text stay linear.
+    TwoDimensional,
+}
+
+/// Operators written with spaces around them.
+const SPACED_OPERATORS: [&str; 20] = [
+    "+", "-", "−", "=", "<", ">", "≤", "≥", "≠", "≈", "≡", "×", "÷", "·", "±", "∓", "→", "⇒", "⇔",
+    "∈",
+];
+
+/// Formula of a `math` element on one line, its `alttext` when nothing readable is in it.
+pub fn linear(doc: &Document, id: NodeId) -> String {
+    let text = to_linear(doc, id).trim().to_owned();
+    match text.is_empty() {
+        true => doc.attr(id, "alttext").unwrap_or("").trim().to_owned(),
+        false => text,
+    }
+}
+
+fn to_linear(doc: &Document, id: NodeId) -> String {
+    let Some(name) = doc.name(id) else {
+        return String::new();
+    };
+    let children = elements(doc, id);
+    let child = |i: usize| {
+        children
+            .get(i)
+            .map(|child| to_linear(doc, *child))
+            .unwrap_or_default()
+    };
+    let row = |children: &[NodeId]| {
+        children
+            .iter()
+            .map(|child| to_linear(doc, *child))
+            .collect::<String>()
+    };
+    match name {
+        "mi" | "mn" | "mtext" => doc.text_content(id).trim().to_owned(),
+        "ms" => format!("\"{}\"", doc.text_content(id).trim()),
+        "mo" => operator(doc.text_content(id).trim()),
+        "mspace" => " ".to_owned(),
+        "mfrac" => format!("{}/{}", group(&child(0)), group(&child(1))),
+        "msqrt" => format!("sqrt({})", row(&children)),
+        "mroot" => format!("root({}, {})", child(0), child(1)),
+        "msup" | "mover" => format!("{}^{}", group(&child(0)), group(&child(1))),
+        "msub" | "munder" => format!("{}_{}", group(&child(0)), group(&child(1))),
+        "msubsup" | "munderover" => format!(
+            "{}_{}^{}",
+            group(&child(0)),
+            group(&child(1)),
+            group(&child(2))
+        ),
+        "mfenced" => {
+            let open = doc.attr(id, "open").unwrap_or("(");
+            let close = doc.attr(id, "close").unwrap_or(")");
+            let separator = doc.attr(id, "separators").unwrap_or(",").trim();
+            let items: Vec<String> = children.iter().map(|c| to_linear(doc, *c)).collect();
+            format!(
+                "{}{}{}",
+                open,
+                items.join(&format!("{} ", separator)),
+                close
+            )
+        }
+        "mtable" => {
+            let rows: Vec<String> = children.iter().map(|c| to_linear(doc, *c)).collect();
+            format!("[{}]", rows.join("; "))
+        }
+        "mtr" | "mlabeledtr" => {
+            let cells: Vec<String> = children.iter().map(|c| to_linear(doc, *c)).collect();
+            cells.join(", ")
+        }
+        "semantics" => child(0),
+        "annotation" | "annotation-xml" | "mphantom" => String::new(),
+        _ => row(&children),
+    }
+}
+
+/// Operator with the spacing it's read with.
+fn operator(text: &str) -> String {
+    match text {
+        _ if SPACED_OPERATORS.contains(&text) => format!(" {} ", text),
+        "," | ";" => format!("{} ", text),
+        _ => text.to_owned(),
+    }
+}
+
+/// Operand of a fraction or script, in parentheses unless it reads as one thing.
+fn group(text: &str) -> String {
+    let text = text.trim();
+    match is_atomic(text) {
+        true => text.to_owned(),
+        false => format!("({})", text),
+    }
+}
+
+/// Whether the text is a name, a number or a single call like `sqrt(x)`.
+fn is_atomic(text: &str) -> bool {
+    if text.chars().all(|c| c.is_alphanumeric() || c == '.') {
+        return true;
+    }
+    let Some(inner) = text.strip_suffix(')') else {
+        return false;
+    };
+    // The parenthesis closing at the end has to open right after a name
+    let mut depth = 1;
+    for (index, c) in inner.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return inner[..index].chars().all(char::is_alphabetic);
+        }
+    }
+    false
+}
+
+/// Element children, without the whitespace between them.
+fn elements(doc: &Document, id: NodeId) -> Vec<NodeId> {
+    doc.children(id)
+        .iter()
+        .copied()
+        .filter(|child| doc.name(*child).is_some())
+        .collect()
+}
+
+/// Formula of a `math` element drawn over several lines, empty when there's nothing
+/// readable in it.
+pub fn two_dimensional(doc: &Document, id: NodeId) -> Vec<String> {
+    let drawn = to_box(doc, id);
+    let lines: Vec<String> = drawn
+        .lines
+        .iter()
+        .map(|line| line.trim_end().to_owned())
+        .collect();
+    match lines.iter().all(|line| line.is_empty()) {
+        true => vec![],
+        false => lines,
+    }
+}
+
+/// Rectangle of text with the row other boxes line up with.
+#[derive(Debug, Clone)]
+struct TextBox {
+    /// Lines padded to the same width.
+    lines: Vec<String>,
+    baseline: usize,
+}
+
+impl TextBox {
+    fn text(text: &str) -> Self {
+        Self {
+            lines: vec![text.to_owned()],
+            baseline: 0,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.lines.first().map_or(0, |line| width(line))
+    }
+
+    fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Rows below the baseline.
+    fn descent(&self) -> usize {
+        self.height() - self.baseline - 1
+    }
+}
+
+fn to_box(doc: &Document, id: NodeId) -> TextBox {
+    let Some(name) = doc.name(id) else {
+        return TextBox::text("");
+    };
+    let children = elements(doc, id);
+    let child = |i: usize| {
+        children
+            .get(i)
+            .map(|child| to_box(doc, *child))
+            .unwrap_or_else(|| TextBox::text(""))
+    };
+    match name {
+        "mi" | "mn" | "mtext" | "ms" | "mo" | "mspace" => TextBox::text(&to_linear(doc, id)),
+        "mfrac" => fraction(child(0), child(1)),
+        "msqrt" => root(row(doc, &children)),
+        "mroot" => concat(vec![
+            scripts(TextBox::text(""), None, Some(child(1))),
+            root(child(0)),
+        ]),
+        "msup" => scripts(child(0), None, Some(child(1))),
+        "msub" => scripts(child(0), Some(child(1)), None),
+        "msubsup" => scripts(child(0), Some(child(1)), Some(child(2))),
+        "mover" => stack(child(0), None, Some(child(1))),
+        "munder" => stack(child(0), Some(child(1)), None),
+        "munderover" => stack(child(0), Some(child(1)), Some(child(2))),
+        "mfenced" => {
+            let open = doc.attr(id, "open").unwrap_or("(");
+            let close = doc.attr(id, "close").unwrap_or(")");
+            let separator = format!("{} ", doc.attr(id, "separators").unwrap_or(",").trim());
+            let mut boxes = vec![TextBox::text(open)];
+            for (i, child) in children.iter().enumerate() {
+                if i > 0 {
+                    boxes.push(TextBox::text(&separator));
+                }
+                boxes.push(to_box(doc, *child));
+            }
+            boxes.push(TextBox::text(close));
+            stretch_fences(boxes)
+        }
+        "mtable" => table(doc, &children),
+        "semantics" => child(0),
+        "annotation" | "annotation-xml" | "mphantom" => TextBox::text(""),
+        _ => row(doc, &children),
+    }
+}
+
+fn row(doc: &Document, children: &[NodeId]) -> TextBox {
+    stretch_fences(children.iter().map(|child| to_box(doc, *child)).collect())
+}
+
+/// Puts boxes side by side, parentheses and brackets growing as tall as the rest.
+fn stretch_fences(boxes: Vec<TextBox>) -> TextBox {
+    let is_fence = |b: &TextBox| b.height() == 1 && fence_parts(b.lines[0].trim()).is_some();
+    let ascent = boxes
+        .iter()
+        .filter(|b| !is_fence(b))
+        .map(|b| b.baseline)
+        .max()
+        .unwrap_or(0);
+    let descent = boxes
+        .iter()
+        .filter(|b| !is_fence(b))
+        .map(|b| b.descent())
+        .max()
+        .unwrap_or(0);
+    let boxes = boxes
+        .into_iter()
+        .map(|b| match is_fence(&b) && ascent + descent > 0 {
+            true => tall_fence(b.lines[0].trim(), ascent, descent),
+            false => b,
+        })
+        .collect();
+    concat(boxes)
+}
+
+/// Top, middle, bottom and centre pieces a fence is drawn with when it's tall.
+fn fence_parts(fence: &str) -> Option<[char; 4]> {
+    let parts = match fence {
+        "(" => ['⎛', '⎜', '⎝', '⎜'],
+        ")" => ['⎞', '⎟', '⎠', '⎟'],
+        "[" => ['⎡', '⎢', '⎣', '⎢'],
+        "]" => ['⎤', '⎥', '⎦', '⎥'],
+        "{" => ['⎧', '⎪', '⎩', '⎨'],
+        "}" => ['⎫', '⎪', '⎭', '⎬'],
+        "|" => ['│', '│', '│', '│'],
+        _ => return None,
+    };
+    Some(parts)
+}
+
+fn tall_fence(fence: &str, ascent: usize, descent: usize) -> TextBox {
+    let [top, middle, bottom, center] = fence_parts(fence).unwrap();
+    let height = ascent + descent + 1;
+    let lines = (0..height)
+        .map(|row| match row {
+            0 => top,
+            _ if row == height - 1 => bottom,
+            _ if row == ascent => center,
+            _ => middle,
+        })
+        .map(String::from)
+        .collect();
+    TextBox {
+        lines,
+        baseline: ascent,
+    }
+}
+
+/// Boxes side by side, lined up on their baselines.
+fn concat(boxes: Vec<TextBox>) -> TextBox {
+    let ascent = boxes.iter().map(|b| b.baseline).max().unwrap_or(0);
+    let descent = boxes.iter().map(|b| b.descent()).max().unwrap_or(0);
+    let mut lines = vec![String::new(); ascent + descent + 1];
+    for b in &boxes {
+        let blank = " ".repeat(b.width());
+        for (row, line) in lines.iter_mut().enumerate() {
+            let text = (row + b.baseline)
+                .checked_sub(ascent)
+                .and_then(|row| b.lines.get(row))
+                .unwrap_or(&blank);
+            line.push_str(text);
+        }
+    }
+    TextBox {
+        lines,
+        baseline: ascent,
+    }
+}
+
+fn fraction(numerator: TextBox, denominator: TextBox) -> TextBox {
+    let width = numerator.width().max(denominator.width());
+    let baseline = numerator.height();
+    let mut lines: Vec<String> = numerator.lines.iter().map(|l| center(l, width)).collect();
+    lines.push("─".repeat(width));
+    lines.extend(denominator.lines.iter().map(|l| center(l, width)));
+    TextBox { lines, baseline }
+}
+
+fn root(radicand: TextBox) -> TextBox {
+    let last = radicand.height() - 1;
+    let mut lines = vec![format!(" {}", "_".repeat(radicand.width()))];
+    for (row, line) in radicand.lines.iter().enumerate() {
+        let sign = match row == last {
+            true => '√',
+            false => '│',
+        };
+        lines.push(format!("{}{}", sign, line));
+    }
+    TextBox {
+        lines,
+        baseline: radicand.baseline + 1,
+    }
+}
+
+/// Base with scripts on its right, the superscript above and the subscript below it.
+fn scripts(base: TextBox, sub: Option<TextBox>, sup: Option<TextBox>) -> TextBox {
+    let script_width = [&sub, &sup]
+        .iter()
+        .filter_map(|script| script.as_ref().map(TextBox::width))
+        .max()
+        .unwrap_or(0);
+    let base_blank = " ".repeat(base.width());
+    let mut lines = vec![];
+    if let Some(sup) = &sup {
+        lines.extend(
+            sup.lines
+                .iter()
+                .map(|l| base_blank.clone() + &pad(l, script_width)),
+        );
+    }
+    let baseline = lines.len() + base.baseline;
+    lines.extend(
+        base.lines
+            .iter()
+            .map(|l| format!("{}{}", l, " ".repeat(script_width))),
+    );
+    if let Some(sub) = &sub {
+        lines.extend(
+            sub.lines
+                .iter()
+                .map(|l| base_blank.clone() + &pad(l, script_width)),
+        );
+    }
+    TextBox { lines, baseline }
+}
+
+/// Base with what's under and over it centered on it.
+fn stack(base: TextBox, under: Option<TextBox>, over: Option<TextBox>) -> TextBox {
+    let width = [Some(&base), under.as_ref(), over.as_ref()]
+        .iter()
+        .flatten()
+        .map(|b| b.width())
+        .max()
+        .unwrap_or(0);
+    let mut lines = vec![];
+    if let Some(over) = &over {
+        lines.extend(over.lines.iter().map(|l| center(l, width)));
+    }
+    let baseline = lines.len() + base.baseline;
+    lines.extend(base.lines.iter().map(|l| center(l, width)));
+    if let Some(under) = &under {
+        lines.extend(under.lines.iter().map(|l| center(l, width)));
+    }
+    TextBox { lines, baseline }
+}
+
+/// Cells of the table in columns two cells apart, lined up at the top of every row.
+fn table(doc: &Document, rows: &[NodeId]) -> TextBox {
+    let cells: Vec<Vec<TextBox>> = rows
+        .iter()
+        .map(|row| {
+            elements(doc, *row)
+                .into_iter()
+                .map(|cell| to_box(doc, cell))
+                .collect()
+        })
+        .collect();
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            cells
+                .iter()
+                .filter_map(|row| row.get(column).map(TextBox::width))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut lines = vec![];
+    for row in &cells {
+        let height = row.iter().map(TextBox::height).max().unwrap_or(1);
+        for line in 0..height {
+            let texts: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, width)| {
+                    let text = row
+                        .get(column)
+                        .and_then(|cell| cell.lines.get(line))
+                        .map_or("", String::as_str);
+                    center(text, *width)
+                })
+                .collect();
+            lines.push(texts.join("  "));
+        }
+    }
+    if lines.is_empty() {
+        return TextBox::text("");
+    }
+    TextBox {
+        baseline: (lines.len() - 1) / 2,
+        lines,
+    }
+}
+
+fn pad(text: &str, cells: usize) -> String {
+    format!("{}{}", text, " ".repeat(cells.saturating_sub(width(text))))
+}
+
+fn center(text: &str, cells: usize) -> String {
+    let free = cells.saturating_sub(width(text));
+    format!(
+        "{}{}{}",
+        " ".repeat(free / 2),
+        text,
+        " ".repeat(free - free / 2)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::Document;
+    use crate::math::{linear, two_dimensional};
+
+    fn math(source: &str) -> (Document, usize) {
+        let doc = Document::parse(source);
+        let id = doc.find_element("math").unwrap();
+        (doc, id)
+    }
+
+    #[test]
+    fn formulas_read_on_one_line() {
+        let (doc, id) = math(
+            "<math><mrow><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo>\
+             <mfrac><msqrt><mi>y</mi></msqrt><mn>2</mn></mfrac></mrow></math>",
+        );
+        assert_eq!(linear(&doc, id), "x^2 + sqrt(y)/2");
+
+        let (doc, id) =
+            math("<math><msub><mi>a</mi><mrow><mi>i</mi><mo>+</mo><mn>1</mn></mrow></msub></math>");
+        assert_eq!(linear(&doc, id), "a_(i + 1)");
+
+        let (doc, id) = math(r#"<math alttext="\alpha"><annotation>a</annotation></math>"#);
+        assert_eq!(linear(&doc, id), "\\alpha");
+    }
+
+    #[test]
+    fn formulas_are_drawn_over_lines() {
+        let (doc, id) = math(
+            "<math><mfrac><mrow><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><mn>1</mn></mrow>\
+             <mn>2</mn></mfrac></math>",
+        );
+        assert_eq!(
+            two_dimensional(&doc, id),
+            vec![" 2", "x  + 1", "──────", "  2"]
+        );
+
+        let (doc, id) = math(
+            "<math><mrow><mo>(</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>0</mn></mtd></mtr>\
+             <mtr><mtd><mn>0</mn></mtd><mtd><mn>1</mn></mtd></mtr></mtable><mo>)</mo></mrow></math>",
+        );
+        assert_eq!(two_dimensional(&doc, id), vec!["⎛1  0⎞", "⎝0  1⎠"]);
+    }
+}
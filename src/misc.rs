@@ -4,9 +4,18 @@ use std::option::Option::{None, Some};
 use std::path::{Path, PathBuf};
 use std::result::Result;
 
+#[allow(clippy::enum_variant_names)]
 pub enum ReaderState {
+    /// Cover, title and author shown when the book is opened, any key moves to the TOC.
+    CoverShown,
     TocShown,
     ContentShown,
+    /// Notes drawn over the content, any key closes them.
+    NoteShown,
+    /// Labels drawn over links, typing one follows its link.
+    HintsShown,
+    /// An image over the whole screen, `back` closes it.
+    ImageShown,
 }
 
 pub enum MoveDirection {
@@ -26,6 +35,39 @@ impl Toc {
     }
 }
 
+/// Place in the book, the file shown and its first line on the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub src: String,
+    pub line: u16,
+}
+
+/// Positions left by following links, walked back and forth like in a web browser.
+#[derive(Default)]
+pub struct History {
+    back: Vec<Position>,
+    forward: Vec<Position>,
+}
+impl History {
+    /// Remembers where a link was followed from, forgetting what was ahead of it.
+    pub fn visit(&mut self, from: Position) {
+        self.back.push(from);
+        self.forward.clear();
+    }
+
+    pub fn back(&mut self, current: Position) -> Option<Position> {
+        let previous = self.back.pop()?;
+        self.forward.push(current);
+        Some(previous)
+    }
+
+    pub fn forward(&mut self, current: Position) -> Option<Position> {
+        let next = self.forward.pop()?;
+        self.back.push(current);
+        Some(next)
+    }
+}
+
 pub struct Zipper;
 impl<'a> Zipper {
     pub fn unzip(file_path: &str, unzip_location: &Path) -> Result<(), &'a str> {
@@ -46,7 +88,7 @@ impl<'a> Zipper {
                 println!("File {} comment: {}", i, comment);
             }
 
-            if (&*file.name()).ends_with('/') {
+            if file.name().ends_with('/') {
                 println!("File {} extracted to \"{}\"", i, outpath.display());
                 fs::create_dir_all(&outpath).expect(err_msg);
             } else {
@@ -58,7 +100,7 @@ impl<'a> Zipper {
                 );
                 if let Some(p) = outpath.parent() {
                     if !p.exists() {
-                        fs::create_dir_all(&p).expect(err_msg);
+                        fs::create_dir_all(p).expect(err_msg);
                     }
                 }
                 let mut outfile = fs::File::create(&outpath).expect(err_msg);
@@ -68,3 +110,29 @@ impl<'a> Zipper {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::misc::{History, Position};
+
+    fn at(src: &str, line: u16) -> Position {
+        Position {
+            src: src.to_owned(),
+            line,
+        }
+    }
+
+    #[test]
+    fn history_goes_back_and_forward() {
+        let mut history = History::default();
+        history.visit(at("a", 10));
+        history.visit(at("b", 0));
+        assert_eq!(history.back(at("c", 5)), Some(at("b", 0)));
+        assert_eq!(history.back(at("b", 0)), Some(at("a", 10)));
+        assert_eq!(history.back(at("a", 10)), None);
+        assert_eq!(history.forward(at("a", 10)), Some(at("b", 0)));
+
+        history.visit(at("b", 0));
+        assert_eq!(history.forward(at("d", 0)), None);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::align::{align, Alignment};
+use crate::graphics::{CellSize, Graphics};
+use crate::html::LayoutOptions;
+use crate::line::{ImageRow, Line, Style};
+use crate::wrap::width;
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+
+/// Pixels of the image a terminal cell is about as wide as, so small images aren't
+/// blown up to the full width of the text.
+const PIXELS_PER_CELL: u32 = 8;
+
+/// Cell of half blocks, showing two square pixels.
+pub const HALF_BLOCK_CELL: CellSize = CellSize {
+    width: PIXELS_PER_CELL,
+    height: PIXELS_PER_CELL * 2,
+};
+
+/// Image of the book, drawn by the terminal, with half blocks or as its `alt` text.
+pub struct Picture {
+    /// Path of the image file.
+    pub path: String,
+    pub alt: String,
+}
+
+impl Picture {
+    /// Width the image is drawn at when there's enough space, or of its placeholder.
+    pub fn natural_width(&self) -> usize {
+        match image::image_dimensions(&self.path) {
+            Ok((width, _)) => width.div_ceil(PIXELS_PER_CELL) as usize,
+            Err(_) => width(&self.placeholder_text()),
+        }
+    }
+
+    pub fn layout(&self, max_width: usize, options: &LayoutOptions, out: &mut Vec<Line>) {
+        self.layout_fit(max_width, usize::MAX, options, out);
+    }
+
+    /// Lays the image out like `layout`, narrower when it'd take more than `max_rows`.
+    pub fn layout_fit(
+        &self,
+        max_width: usize,
+        max_rows: usize,
+        options: &LayoutOptions,
+        out: &mut Vec<Line>,
+    ) {
+        let cell = match options.images {
+            Graphics::None => return out.push(self.placeholder(max_width)),
+            Graphics::HalfBlocks => HALF_BLOCK_CELL,
+            Graphics::Kitty(cell) | Graphics::Sixel(cell) => cell,
+        };
+        let Ok(dimensions) = image::image_dimensions(&self.path) else {
+            return out.push(self.placeholder(max_width));
+        };
+        let (columns, rows) = fit(dimensions, cell, max_width, max_rows);
+        match options.images {
+            Graphics::HalfBlocks => self.layout_half_blocks(columns, rows, max_width, out),
+            _ => self.reserve(columns, rows, max_width, out),
+        }
+    }
+
+    /// Empty lines the terminal draws the image over.
+    fn reserve(&self, columns: usize, rows: usize, max_width: usize, out: &mut Vec<Line>) {
+        for row in 0..rows {
+            let mut line = Line::new();
+            line.push(&" ".repeat(columns), &Style::default());
+            let mut line = align(line, Alignment::Center, max_width, true);
+            line.image = Some(self.row(max_width, columns, row, rows));
+            out.push(line);
+        }
+    }
+
+    /// Where the line shows the image, for the terminal to draw it or to open it.
+    fn row(&self, max_width: usize, columns: usize, row: usize, rows: usize) -> ImageRow {
+        ImageRow {
+            path: self.path.clone(),
+            column: (max_width.saturating_sub(columns)) / 2,
+            columns,
+            row,
+            rows,
+        }
+    }
+
+    fn layout_half_blocks(
+        &self,
+        columns: usize,
+        rows: usize,
+        max_width: usize,
+        out: &mut Vec<Line>,
+    ) {
+        let Ok(decoded) = image::open(&self.path) else {
+            return out.push(self.placeholder(max_width));
+        };
+        // Every cell shows two square pixels, one above the other
+        let scaled = decoded
+            .resize_exact(columns as u32, rows as u32 * 2, FilterType::Triangle)
+            .to_rgba8();
+        for (row, line) in half_blocks(&scaled).into_iter().enumerate() {
+            let mut line = align(line, Alignment::Center, max_width, true);
+            line.image = Some(self.row(max_width, columns, row, rows));
+            out.push(line);
+        }
+    }
+
+    fn placeholder_text(&self) -> String {
+        let alt = self.alt.trim();
+        match alt.is_empty() {
+            true => "[image]".to_owned(),
+            false => format!("[{}]", alt),
+        }
+    }
+
+    fn placeholder(&self, max_width: usize) -> Line {
+        let text = self.placeholder_text();
+        let mut line = Line::new();
+        line.push(&text, &Style::new("image_alt"));
+        let mut line = align(line, Alignment::Center, max_width, true);
+        line.image = Some(self.row(max_width, width(&text), 0, 1));
+        line
+    }
+}
+
+/// Columns and rows of cells the image takes, a cell for every `cell` of its pixels but
+/// no more than fits.
+fn fit(
+    (width, height): (u32, u32),
+    cell: CellSize,
+    max_columns: usize,
+    max_rows: usize,
+) -> (usize, usize) {
+    let natural = width.div_ceil(cell.width) as usize;
+    fill((width, height), cell, natural.min(max_columns), max_rows)
+}
+
+/// Columns and rows of cells the image takes scaled up or down to the most that fits.
+pub fn fill(
+    (width, height): (u32, u32),
+    cell: CellSize,
+    max_columns: usize,
+    max_rows: usize,
+) -> (usize, usize) {
+    let rows_for = |columns: u64| {
+        (height as u64 * columns * cell.width as u64)
+            .div_ceil(width.max(1) as u64 * cell.height as u64)
+            .max(1)
+    };
+    let max_rows = max_rows.max(1) as u64;
+    let mut columns = max_columns.max(1) as u64;
+    if rows_for(columns) > max_rows {
+        columns = (columns * max_rows / rows_for(columns)).max(1);
+    }
+    // Rows are rounded up, so it can still be a bit too tall
+    while columns > 1 && rows_for(columns) > max_rows {
+        columns -= 1;
+    }
+    (columns as usize, rows_for(columns).min(max_rows) as usize)
+}
+
+/// Lines drawing the image with `▀`, the upper pixel of a cell as the foreground and the
+/// lower one as the background. Transparent pixels are left to the terminal background.
+pub fn half_blocks(image: &RgbaImage) -> Vec<Line> {
+    let mut lines = vec![];
+    for y in (0..image.height()).step_by(2) {
+        let mut line = Line::new();
+        for x in 0..image.width() {
+            let upper = opaque(image.get_pixel(x, y));
+            let lower = match y + 1 < image.height() {
+                true => opaque(image.get_pixel(x, y + 1)),
+                false => None,
+            };
+            let (cell, keys) = match (upper, lower) {
+                (Some(upper), Some(lower)) => (
+                    "▀",
+                    vec![format!("color:{}", upper), format!("background:{}", lower)],
+                ),
+                (Some(upper), None) => ("▀", vec![format!("color:{}", upper)]),
+                (None, Some(lower)) => ("▄", vec![format!("color:{}", lower)]),
+                (None, None) => (" ", vec![]),
+            };
+            line.push(
+                cell,
+                &Style {
+                    keys,
+                    ..Style::default()
+                },
+            );
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Colour of a pixel as `#rrggbb`, `None` when it's mostly transparent.
+fn opaque(pixel: &Rgba<u8>) -> Option<String> {
+    let [r, g, b, a] = pixel.0;
+    match a >= 128 {
+        true => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::CellSize;
+    use crate::picture::{fit, half_blocks};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn images_shrink_to_fit() {
+        let cell = CellSize {
+            width: 10,
+            height: 20,
+        };
+        assert_eq!(fit((100, 200), cell, 80, usize::MAX), (10, 10));
+        assert_eq!(fit((1000, 200), cell, 80, usize::MAX), (80, 8));
+        assert_eq!(fit((100, 200), cell, 80, 4), (4, 4));
+    }
+
+    #[test]
+    fn two_pixels_share_a_cell() {
+        let mut image = RgbaImage::new(2, 3);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
+        image.put_pixel(1, 1, Rgba([0, 255, 0, 255]));
+        image.put_pixel(0, 2, Rgba([255, 255, 255, 255]));
+
+        let lines = half_blocks(&image);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text(), "▀▄");
+        assert_eq!(
+            lines[0].spans[0].style.keys,
+            vec!["color:#ff0000", "background:#0000ff"]
+        );
+        assert_eq!(lines[0].spans[1].style.keys, vec!["color:#00ff00"]);
+        assert_eq!(lines[1].text(), "▀ ");
+    }
+}
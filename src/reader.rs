@@ -1,20 +1,34 @@
+use crate::align::{align, Alignment};
+use crate::bidi::Direction;
 use crate::config::ReaderConfig;
-use crate::html::HtmlToLine;
-use crate::misc::{ReaderState, Toc, Zipper};
+use crate::cover::Cover;
+use crate::graphics::Graphics;
+use crate::hints::labels;
+use crate::html::{anchor_line, is_external, resolve_link, scheme, HtmlToLine, LayoutOptions};
+use crate::line::{Line, Style};
+use crate::misc::{History, Position, ReaderState, Toc, Zipper};
+use crate::picture::{fill, half_blocks, Picture, HALF_BLOCK_CELL};
 use crate::styler::Styler;
 use crate::styler::TagStyler;
+use crate::styler::Theme;
 use crate::styler::TocStyler;
 use crate::term::{TermSize, Terminal, TermionTerminal};
-use crossterm::terminal::enable_raw_mode;
+use crate::viewer::ImageViewer;
+use crate::wrap::width;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use crossterm::ExecutableCommand;
+use image::imageops::{self, FilterType};
 use regex::Regex;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::error;
 use std::fs;
 use std::fs::File;
-use std::io::{stdin, stdout, ErrorKind, Write};
+use std::io::{stdout, ErrorKind, Write};
 use std::option::Option::{None, Some};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::thread;
 use std::time::Duration;
 use xmltree::Element;
@@ -25,17 +39,60 @@ pub struct EpubReader<'a> {
     state: ReaderState,
     term: Box<dyn Terminal>,
     config: ReaderConfig<'a>,
-    loaded_lines: Vec<String>,
+    loaded_lines: Vec<Line>,
+    language: Option<String>,
+    /// Notes shown over the content.
+    note_lines: Vec<Line>,
+    /// File the loaded lines come from.
+    current_src: String,
+    history: History,
+    /// Line and span where the focused link starts.
+    focused_link: Option<(usize, usize)>,
+    /// Labels of the links shown in hint mode, with where the links start.
+    hints: Vec<(String, (usize, usize))>,
+    /// Letters of a hint typed so far.
+    hint_input: String,
+    /// How images are drawn, known once the terminal is asked.
+    graphics: Graphics,
+    cover: Cover,
+    viewer: Option<ImageViewer>,
+    /// Direction pages go in, from the spine. Page keys swap for right-to-left books.
+    direction: Direction,
+    themes: Vec<Theme>,
+    /// Index of the theme in use in `themes`.
+    theme: usize,
+    /// Classes styled by any theme, so switching themes needs no new layout.
+    theme_classes: Vec<String>,
 }
 
+/// Part of the view an image moves by when panned.
+const PAN_STEP: f32 = 0.25;
+
+/// Cells taken by the border and padding around notes.
+const NOTE_CHROME: u16 = 4;
+
 impl<'a> EpubReader<'a> {
-    pub fn new() -> Self {
+    pub fn new(config: ReaderConfig<'a>) -> Self {
         EpubReader {
             toc: vec![],
             state: ReaderState::TocShown,
-            term: Box::new(TermionTerminal::new()),
-            config: ReaderConfig::new(30, 5, "./tmp"),
+            term: Box::new(TermionTerminal::new(config.hyperlinks, config.graphics)),
+            config,
             loaded_lines: vec![],
+            language: None,
+            note_lines: vec![],
+            current_src: String::new(),
+            history: History::default(),
+            focused_link: None,
+            hints: vec![],
+            hint_input: String::new(),
+            graphics: Graphics::None,
+            cover: Cover::default(),
+            viewer: None,
+            direction: Direction::Ltr,
+            themes: vec![],
+            theme: 0,
+            theme_classes: vec![],
         }
     }
 
@@ -50,8 +107,7 @@ impl<'a> EpubReader<'a> {
         }
 
         if epub_file_path.is_dir() {
-            return Err(Box::new(std::io::Error::new(
-                ErrorKind::Other,
+            return Err(Box::new(std::io::Error::other(
                 "File path provided leads to something other than file",
             )));
         }
@@ -62,13 +118,12 @@ impl<'a> EpubReader<'a> {
         let extract_path = Path::new(&extract_str);
 
         if !extract_path.exists() {
-            fs::create_dir_all(extract_path).expect(&format!(
-                "Failed to create temporary folder at: {}",
-                extract_str
-            ));
+            fs::create_dir_all(extract_path).unwrap_or_else(|_| {
+                panic!("Failed to create temporary folder at: {}", extract_str)
+            });
 
             //@ Dependency: Replace Zipper with our own functions and structs
-            Zipper::unzip(file_path, &extract_path).expect("Failed to unzip file");
+            Zipper::unzip(file_path, extract_path).expect("Failed to unzip file");
         }
 
         //Get content.opf location
@@ -102,6 +157,22 @@ impl<'a> EpubReader<'a> {
             .captures(&content_opf)
             .expect("Could't find toc lick in content.opf")[0];
 
+        self.cover = Cover::from_opf(&content_opf, content_path.to_str().unwrap());
+
+        self.direction = match Regex::new(r#"<spine[^>]*page-progression-direction="rtl""#)
+            .unwrap()
+            .is_match(&content_opf)
+        {
+            true => Direction::Rtl,
+            false => Direction::Ltr,
+        };
+
+        //Get book language, used to hyphenate
+        self.language = Regex::new(r"<dc:language[^>]*>\s*(.*?)\s*</dc:language>")
+            .unwrap()
+            .captures(&content_opf)
+            .map(|captures| captures[1].to_owned());
+
         let toc_path = match Regex::new("<.*href=\"(.*?)\".*/>")
             .unwrap()
             .captures(toc_tag)
@@ -142,39 +213,37 @@ impl<'a> EpubReader<'a> {
                 .children
             {
                 match el {
-                    ElementEnum(element) => {
-                        if element.name == "navPoint" {
-                            let text = element
-                                .get_child("navLabel")
-                                .expect("Cannot find navLabel inside of one of navPoints")
-                                .get_child("text")
-                                .expect("Cannot find text inside of one of navLabel")
-                                .children[0]
-                                .as_text()
-                                .expect("text tag inside of navLabel do not have content specifed");
-
-                            let src = &element
-                                .get_child("content")
-                                .expect("Cannot find content inside of navLabel")
-                                .attributes["src"];
-
-                            let split: Vec<&str> = src.split("#").collect();
-
-                            match split.len() {
-                                2 => {
-                                    self.toc.push(Toc::new(
-                                        format!("{}/{}", up_to_toc, split[0]),
-                                        split[1].to_owned(),
-                                        text.to_owned(),
-                                    ));
-                                }
-                                _ => {
-                                    self.toc.push(Toc::new(
-                                        format!("{}/{}", up_to_toc, split[0]),
-                                        String::from(""),
-                                        text.to_owned(),
-                                    ));
-                                }
+                    ElementEnum(element) if element.name == "navPoint" => {
+                        let text = element
+                            .get_child("navLabel")
+                            .expect("Cannot find navLabel inside of one of navPoints")
+                            .get_child("text")
+                            .expect("Cannot find text inside of one of navLabel")
+                            .children[0]
+                            .as_text()
+                            .expect("text tag inside of navLabel do not have content specifed");
+
+                        let src = &element
+                            .get_child("content")
+                            .expect("Cannot find content inside of navLabel")
+                            .attributes["src"];
+
+                        let split: Vec<&str> = src.split("#").collect();
+
+                        match split.len() {
+                            2 => {
+                                self.toc.push(Toc::new(
+                                    format!("{}/{}", up_to_toc, split[0]),
+                                    split[1].to_owned(),
+                                    text.to_owned(),
+                                ));
+                            }
+                            _ => {
+                                self.toc.push(Toc::new(
+                                    format!("{}/{}", up_to_toc, split[0]),
+                                    String::from(""),
+                                    text.to_owned(),
+                                ));
                             }
                         }
                     }
@@ -191,22 +260,49 @@ impl<'a> EpubReader<'a> {
         let mut terminal_size = self.term.get_size()?;
 
         //TODO: Move
+        let theme_file = self.config.theme_file.map(fs::read_to_string).transpose()?;
+        self.themes = Theme::all(theme_file.as_deref());
+        self.theme = self
+            .themes
+            .iter()
+            .position(|theme| theme.name == self.config.theme)
+            .unwrap_or(0);
+        let mut classes: Vec<String> = self
+            .themes
+            .iter()
+            .flat_map(|theme| theme.classes().map(str::to_owned))
+            .collect();
+        classes.sort();
+        classes.dedup();
+        self.theme_classes = classes;
+
         enable_raw_mode()?;
+        if self.config.images {
+            self.graphics = self.term.graphics();
+        }
+        if self.config.mouse {
+            stdout().execute(EnableMouseCapture)?;
+        }
 
         let mut toc_screen = stdout();
         let mut content_screen = stdout();
 
         let mut selected_option = 0;
         let mut first_line: u16 = 0;
-        let styler = TagStyler::new();
-        let toc_styler = TocStyler::new();
-
-        self.print_toc(
-            &mut toc_screen,
-            selected_option,
-            &terminal_size,
-            &toc_styler,
-        );
+        let mut styler = TagStyler::new(&self.themes[self.theme]);
+        let mut toc_styler = TocStyler::new(&self.themes[self.theme]);
+
+        if self.cover.is_empty() {
+            self.print_toc(
+                &mut toc_screen,
+                selected_option,
+                &terminal_size,
+                &toc_styler,
+            );
+        } else {
+            self.state = ReaderState::CoverShown;
+            self.print_cover(&mut toc_screen, &terminal_size, &styler);
+        }
 
         let resize_reciver = self
             .term
@@ -218,132 +314,397 @@ impl<'a> EpubReader<'a> {
             .on_input()
             .expect("Failed to get reciver on input channel");
 
+        let click_reciver = self
+            .term
+            .on_click()
+            .expect("Failed to get reciver on click channel");
+
         loop {
-            match resize_reciver.try_recv() {
-                Ok(term_size) => {
-                    terminal_size = term_size;
-                    match self.state {
-                        ReaderState::ContentShown => {
-                            self.print_section(first_line, &mut content_screen, &terminal_size);
+            if let Ok(term_size) = resize_reciver.try_recv() {
+                terminal_size = term_size;
+                self.print_state(
+                    &mut content_screen,
+                    first_line,
+                    selected_option,
+                    &terminal_size,
+                    &styler,
+                    &toc_styler,
+                );
+            }
+
+            if let Ok((column, row)) = click_reciver.try_recv() {
+                match self.state {
+                    ReaderState::ContentShown => match self.link_at(first_line, column, row) {
+                        Some((link, true)) => {
+                            let src = self.current_src.clone();
+                            self.show_notes(
+                                &[link],
+                                &src,
+                                &mut content_screen,
+                                &terminal_size,
+                                &styler,
+                            );
                         }
-                        ReaderState::TocShown => {
-                            self.print_toc(
-                                &mut toc_screen,
-                                selected_option,
+                        Some((link, false)) => {
+                            first_line = self.jump(&link, first_line, &terminal_size);
+                            self.focused_link = None;
+                            self.term.clear(&mut content_screen);
+                            self.print_section(
+                                first_line,
+                                &mut content_screen,
                                 &terminal_size,
-                                &toc_styler,
+                                &styler,
                             );
                         }
+                        None => {}
+                    },
+                    ReaderState::NoteShown => {
+                        self.state = ReaderState::ContentShown;
+                        self.term.clear(&mut content_screen);
+                        self.print_section(
+                            first_line,
+                            &mut content_screen,
+                            &terminal_size,
+                            &styler,
+                        );
                     }
+                    _ => {}
                 }
-                _ => {}
             }
 
-            match input_reciver.try_recv() {
-                Ok(key) => {
-                    if key == self.config.keys.up {
-                        match self.state {
-                            ReaderState::TocShown => {
-                                if selected_option != 0 {
-                                    selected_option -= 1
-                                }
-                                self.print_toc(
-                                    &mut toc_screen,
-                                    selected_option,
-                                    &terminal_size,
-                                    &toc_styler,
-                                );
-                            }
-                            _ => {}
+            if let Ok(key) = input_reciver.try_recv() {
+                // Pages of right-to-left books turn the other way
+                let keys = &self.config.keys;
+                let key = match (&self.state, self.direction) {
+                    (ReaderState::ContentShown, Direction::Rtl) if key == keys.left => keys.right,
+                    (ReaderState::ContentShown, Direction::Rtl) if key == keys.right => keys.left,
+                    _ => key,
+                };
+                if let ReaderState::CoverShown = self.state {
+                    self.state = ReaderState::TocShown;
+                    self.print_toc(
+                        &mut toc_screen,
+                        selected_option,
+                        &terminal_size,
+                        &toc_styler,
+                    );
+                } else if let ReaderState::NoteShown = self.state {
+                    self.state = ReaderState::ContentShown;
+                    self.term.clear(&mut content_screen);
+                    self.print_section(first_line, &mut content_screen, &terminal_size, &styler);
+                } else if let ReaderState::HintsShown = self.state {
+                    self.hint_input.push(key);
+                    let matching: Vec<_> = self
+                        .hints
+                        .iter()
+                        .filter(|(label, _)| label.starts_with(&self.hint_input))
+                        .collect();
+                    let cancelled = key == '\u{1b}' || key == self.config.keys.back;
+                    if cancelled || matching.len() <= 1 {
+                        // The only label left is followed without typing all of it
+                        let target = match (cancelled, matching.first()) {
+                            (false, Some((_, (line, span)))) => self.link_target(*line, *span),
+                            _ => None,
+                        };
+                        self.state = ReaderState::ContentShown;
+                        if let Some((link, false)) = &target {
+                            first_line = self.jump(link, first_line, &terminal_size);
+                        }
+                        self.term.clear(&mut content_screen);
+                        self.print_section(
+                            first_line,
+                            &mut content_screen,
+                            &terminal_size,
+                            &styler,
+                        );
+                        if let Some((link, true)) = target {
+                            let src = self.current_src.clone();
+                            self.show_notes(
+                                &[link],
+                                &src,
+                                &mut content_screen,
+                                &terminal_size,
+                                &styler,
+                            );
                         }
-                    } else if key == self.config.keys.down {
-                        match self.state {
-                            ReaderState::TocShown => {
-                                if selected_option != self.toc.len() - 1 {
-                                    selected_option += 1
+                    } else {
+                        self.print_section(
+                            first_line,
+                            &mut content_screen,
+                            &terminal_size,
+                            &styler,
+                        );
+                        self.print_hints(&mut content_screen, first_line, &styler);
+                    }
+                } else if let ReaderState::ImageShown = self.state {
+                    let keys = self.config.keys;
+                    if key == '\u{1b}' || key == keys.back {
+                        self.viewer = None;
+                        self.state = ReaderState::ContentShown;
+                        self.term.clear(&mut content_screen);
+                        self.print_section(
+                            first_line,
+                            &mut content_screen,
+                            &terminal_size,
+                            &styler,
+                        );
+                    } else {
+                        if let Some(viewer) = &mut self.viewer {
+                            match key {
+                                _ if key == keys.zoom_in => viewer.zoom_in(),
+                                _ if key == keys.zoom_out => viewer.zoom_out(),
+                                _ if key == keys.up => viewer.pan(0.0, -PAN_STEP),
+                                _ if key == keys.down => viewer.pan(0.0, PAN_STEP),
+                                _ if key == keys.left => viewer.pan(-PAN_STEP, 0.0),
+                                _ if key == keys.right => viewer.pan(PAN_STEP, 0.0),
+                                _ if key == keys.open_image => {
+                                    spawn(self.config.image_viewer, &viewer.path)
                                 }
-                                self.print_toc(
-                                    &mut toc_screen,
-                                    selected_option,
-                                    &terminal_size,
-                                    &toc_styler,
-                                );
+                                _ => {}
                             }
-                            _ => {}
                         }
-                    } else if key == self.config.keys.left {
-                        match self.state {
-                            ReaderState::ContentShown => {
-                                if first_line <= 0 {
-                                    continue;
+                        self.print_image(&mut content_screen, &terminal_size, &styler);
+                    }
+                } else if key == self.config.keys.theme {
+                    self.theme = (self.theme + 1) % self.themes.len();
+                    styler = TagStyler::new(&self.themes[self.theme]);
+                    toc_styler = TocStyler::new(&self.themes[self.theme]);
+                    self.term.clear(&mut content_screen);
+                    self.print_state(
+                        &mut content_screen,
+                        first_line,
+                        selected_option,
+                        &terminal_size,
+                        &styler,
+                        &toc_styler,
+                    );
+                } else if key == self.config.keys.image {
+                    if let ReaderState::ContentShown = self.state {
+                        if let Some(path) = self.visible_image(first_line, &terminal_size) {
+                            // Images that can't be decoded may still open elsewhere
+                            match ImageViewer::open(&path) {
+                                Some(viewer) => {
+                                    self.viewer = Some(viewer);
+                                    self.state = ReaderState::ImageShown;
+                                    self.print_image(&mut content_screen, &terminal_size, &styler);
                                 }
-                                first_line -= terminal_size.height - self.config.margin_y * 2;
-                                self.term.clear(&mut content_screen);
-                                self.print_section(first_line, &mut content_screen, &terminal_size);
+                                None => spawn(self.config.image_viewer, &path),
                             }
-                            _ => {}
                         }
-                    } else if key == self.config.keys.right {
-                        match self.state {
-                            ReaderState::ContentShown => {
-                                if usize::from(terminal_size.height) >= self.loaded_lines.len() {
-                                    continue; //We already printed everything in one go
-                                }
+                    }
+                } else if key == self.config.keys.up {
+                    if let ReaderState::TocShown = self.state {
+                        selected_option = selected_option.saturating_sub(1);
+                        self.print_toc(
+                            &mut toc_screen,
+                            selected_option,
+                            &terminal_size,
+                            &toc_styler,
+                        );
+                    }
+                } else if key == self.config.keys.down {
+                    if let ReaderState::TocShown = self.state {
+                        if selected_option != self.toc.len() - 1 {
+                            selected_option += 1
+                        }
+                        self.print_toc(
+                            &mut toc_screen,
+                            selected_option,
+                            &terminal_size,
+                            &toc_styler,
+                        );
+                    }
+                } else if key == self.config.keys.left {
+                    if let ReaderState::ContentShown = self.state {
+                        if first_line == 0 {
+                            continue;
+                        }
+                        // Links and the history can leave less than a page above the first line
+                        first_line = first_line
+                            .saturating_sub(terminal_size.height - self.config.margin_y * 2);
+                        self.focused_link = None;
+                        self.term.clear(&mut content_screen);
+                        self.print_section(
+                            first_line,
+                            &mut content_screen,
+                            &terminal_size,
+                            &styler,
+                        );
+                    }
+                } else if key == self.config.keys.right {
+                    if let ReaderState::ContentShown = self.state {
+                        if usize::from(terminal_size.height) >= self.loaded_lines.len() {
+                            continue; //We already printed everything in one go
+                        }
 
-                                if usize::from(
-                                    first_line + terminal_size.height - self.config.margin_y,
-                                ) >= self.loaded_lines.len()
-                                {
-                                    continue; //We already printed everything in one go
-                                }
+                        if usize::from(first_line + terminal_size.height - self.config.margin_y)
+                            >= self.loaded_lines.len()
+                        {
+                            continue; //We already printed everything in one go
+                        }
 
-                                first_line += terminal_size.height - self.config.margin_y * 2;
-                                self.term.clear(&mut content_screen);
-                                self.print_section(first_line, &mut content_screen, &terminal_size);
-                            }
-                            _ => {}
+                        first_line += terminal_size.height - self.config.margin_y * 2;
+                        self.focused_link = None;
+                        self.term.clear(&mut content_screen);
+                        self.print_section(
+                            first_line,
+                            &mut content_screen,
+                            &terminal_size,
+                            &styler,
+                        );
+                    }
+                } else if key == self.config.keys.select {
+                    match self.state {
+                        ReaderState::TocShown => {
+                            let src = self.toc[selected_option].src.clone();
+                            self.load(&src, &terminal_size);
+                            self.state = ReaderState::ContentShown;
+                            let marker = &self.toc[selected_option].marker;
+                            first_line = anchor_line(&self.loaded_lines, marker)
+                                .and_then(|line| line.try_into().ok())
+                                .unwrap_or(0);
+                            self.term.clear(&mut content_screen);
+                            self.print_section(
+                                first_line,
+                                &mut content_screen,
+                                &terminal_size,
+                                &styler,
+                            );
                         }
-                    } else if key == self.config.keys.select {
-                        match self.state {
-                            ReaderState::TocShown => {
-                                self.loaded_lines = HtmlToLine::as_lines(
-                                    &self.toc[selected_option].src,
-                                    &styler,
-                                    terminal_size.width - (self.config.margin_x * 2),
-                                );
-                                self.state = ReaderState::ContentShown;
-                                first_line = 0;
-                                self.term.clear(&mut content_screen);
-                                self.print_section(first_line, &mut content_screen, &terminal_size);
+                        ReaderState::ContentShown => {
+                            let target = self
+                                .focused_link
+                                .take()
+                                .and_then(|(line, span)| self.link_target(line, span));
+                            if let Some((link, false)) = &target {
+                                first_line = self.jump(link, first_line, &terminal_size);
                             }
-                            _ => {}
-                        }
-                    } else if key == self.config.keys.back {
-                        match self.state {
-                            ReaderState::ContentShown => {
-                                self.term.clear(&mut content_screen);
-                                self.state = ReaderState::TocShown;
-                                self.print_toc(
-                                    &mut toc_screen,
-                                    selected_option,
+                            self.term.clear(&mut content_screen);
+                            self.print_section(
+                                first_line,
+                                &mut content_screen,
+                                &terminal_size,
+                                &styler,
+                            );
+                            if let Some((link, true)) = target {
+                                let src = self.current_src.clone();
+                                self.show_notes(
+                                    &[link],
+                                    &src,
+                                    &mut content_screen,
                                     &terminal_size,
-                                    &toc_styler,
+                                    &styler,
                                 );
                             }
-                            _ => {
-                                self.term.clear(&mut content_screen);
-                                self.term.clear(&mut toc_screen);
-                                break;
+                        }
+                        _ => {}
+                    }
+                } else if key == self.config.keys.link {
+                    if let ReaderState::ContentShown = self.state {
+                        let links = self.visible_links(first_line, &terminal_size);
+                        let next = match self.focused_link {
+                            Some(focused) => links.iter().find(|link| **link > focused),
+                            None => None,
+                        };
+                        self.focused_link = next.or(links.first()).copied();
+                        self.print_section(
+                            first_line,
+                            &mut content_screen,
+                            &terminal_size,
+                            &styler,
+                        );
+                    }
+                } else if key == self.config.keys.hints {
+                    if let ReaderState::ContentShown = self.state {
+                        let links = self.visible_links(first_line, &terminal_size);
+                        let labels = labels(links.len(), self.config.hint_alphabet);
+                        if !labels.is_empty() {
+                            self.hints = labels.into_iter().zip(links).collect();
+                            self.hint_input.clear();
+                            self.focused_link = None;
+                            self.state = ReaderState::HintsShown;
+                            self.print_section(
+                                first_line,
+                                &mut content_screen,
+                                &terminal_size,
+                                &styler,
+                            );
+                            self.print_hints(&mut content_screen, first_line, &styler);
+                        }
+                    }
+                } else if key == self.config.keys.history_back
+                    || key == self.config.keys.history_forward
+                {
+                    if let ReaderState::ContentShown = self.state {
+                        let current = Position {
+                            src: self.current_src.clone(),
+                            line: first_line,
+                        };
+                        let position = match key == self.config.keys.history_back {
+                            true => self.history.back(current),
+                            false => self.history.forward(current),
+                        };
+                        if let Some(position) = position {
+                            if position.src != self.current_src {
+                                self.load(&position.src, &terminal_size);
                             }
+                            // Reloaded at another width the chapter can be shorter
+                            let last = self.loaded_lines.len().try_into().unwrap_or(u16::MAX);
+                            first_line = position.line.min(last);
+                            self.focused_link = None;
+                            self.term.clear(&mut content_screen);
+                            self.print_section(
+                                first_line,
+                                &mut content_screen,
+                                &terminal_size,
+                                &styler,
+                            );
+                        }
+                    }
+                } else if key == self.config.keys.note {
+                    if let ReaderState::ContentShown = self.state {
+                        let links = self.visible_notes(first_line, &terminal_size);
+                        let src = self.current_src.clone();
+                        self.show_notes(&links, &src, &mut content_screen, &terminal_size, &styler);
+                    }
+                } else if key == self.config.keys.back {
+                    match self.state {
+                        ReaderState::ContentShown if self.focused_link.is_some() => {
+                            self.focused_link = None;
+                            self.print_section(
+                                first_line,
+                                &mut content_screen,
+                                &terminal_size,
+                                &styler,
+                            );
+                        }
+                        ReaderState::ContentShown => {
+                            self.term.clear(&mut content_screen);
+                            self.state = ReaderState::TocShown;
+                            self.print_toc(
+                                &mut toc_screen,
+                                selected_option,
+                                &terminal_size,
+                                &toc_styler,
+                            );
+                        }
+                        _ => {
+                            self.term.clear(&mut content_screen);
+                            self.term.clear(&mut toc_screen);
+                            break;
                         }
                     }
                 }
-                _ => {}
             }
 
             thread::sleep(Duration::from_millis(16)); // 60 fps
         }
 
+        // Give the terminal back to the shell as it was
+        if self.config.mouse {
+            stdout().execute(DisableMouseCapture)?;
+        }
+        disable_raw_mode()?;
         Ok(())
     }
 
@@ -353,19 +714,469 @@ impl<'a> EpubReader<'a> {
         Ok(())
     }
 
-    fn print_section<W: Write>(&self, start_line: u16, screen: &mut W, terminal_size: &TermSize) {
+    fn layout_options(&self, terminal_size: &TermSize) -> LayoutOptions<'_> {
+        LayoutOptions {
+            max_chars_in_line: terminal_size.width - (self.config.margin_x * 2),
+            language: self.language.clone(),
+            hyphenation: Some(&self.config.hyphenation),
+            alignment: self.config.alignment,
+            heading_alignment: self.config.heading_alignment,
+            pre_overflow: self.config.pre_overflow,
+            paragraph_spacing: self.config.paragraph_spacing,
+            first_line_indent: self.config.first_line_indent,
+            scene_break: self.config.scene_break,
+            book_colors: self.config.book_colors,
+            images: self.graphics,
+            math: self.config.math,
+            direction: self.direction,
+            bidi: self.config.bidi,
+            ruby: self.config.ruby,
+            classes: &self.theme_classes,
+        }
+    }
+
+    fn load(&mut self, src: &str, terminal_size: &TermSize) {
+        let options = self.layout_options(terminal_size);
+        let lines = HtmlToLine::as_lines(src, &options);
+        self.loaded_lines = lines;
+        self.current_src = src.to_owned();
+    }
+
+    /// Follows a link from the screen starting at `first_line`, remembering where it
+    /// was followed from. Returns the first line to show.
+    fn jump(&mut self, link: &str, first_line: u16, terminal_size: &TermSize) -> u16 {
+        let from = Position {
+            src: self.current_src.clone(),
+            line: first_line,
+        };
+        match self.follow(link, terminal_size) {
+            Some(line) => {
+                self.history.visit(from);
+                line
+            }
+            None => first_line,
+        }
+    }
+
+    /// Opens the target of an internal link, returning the line it points to. External
+    /// links are passed to the opener command, other schemes and missing files are
+    /// ignored.
+    fn follow(&mut self, href: &str, terminal_size: &TermSize) -> Option<u16> {
+        if scheme(href).is_some() {
+            if is_external(href) {
+                self.open_external(href);
+            }
+            return None;
+        }
+        let (file, fragment) = resolve_link(&self.current_src, href);
+        if !Path::new(&file).is_file() {
+            return None;
+        }
+        if file != self.current_src {
+            self.load(&file, terminal_size);
+        }
+        let line = fragment
+            .and_then(|fragment| anchor_line(&self.loaded_lines, &fragment))
+            .unwrap_or(0)
+            .min(self.loaded_lines.len());
+        // A page is added to the first line when printing, it has to stay in range
+        let last = u16::MAX - terminal_size.height;
+        Some(line.try_into().unwrap_or(last).min(last))
+    }
+
+    /// Runs the opener command with the URL, without waiting for it.
+    fn open_external(&self, url: &str) {
+        spawn(self.config.opener, url);
+    }
+
+    /// Path of the image nearest the top of the screen.
+    fn visible_image(&self, start_line: u16, terminal_size: &TermSize) -> Option<String> {
+        let rows = terminal_size.height - self.config.margin_y * 2;
+        self.loaded_lines
+            .iter()
+            .skip(start_line.into())
+            .take(rows.into())
+            .find_map(|line| line.image.as_ref())
+            .map(|image| image.path.clone())
+    }
+
+    /// Line and span of every link starting on the screen, in reading order.
+    fn visible_links(&self, start_line: u16, terminal_size: &TermSize) -> Vec<(usize, usize)> {
+        let rows = terminal_size.height - self.config.margin_y * 2;
+        let start = usize::from(start_line);
+        let end = (start + usize::from(rows)).min(self.loaded_lines.len());
+        let mut links = vec![];
+        for line in start..end {
+            for (span, style) in self.loaded_lines[line]
+                .spans
+                .iter()
+                .map(|span| &span.style)
+                .enumerate()
+            {
+                let Some(link) = &style.link else {
+                    continue;
+                };
+                // Links wrapped or split into differently styled spans start only once
+                let previous = match span {
+                    0 if line > 0 => self.loaded_lines[line - 1].spans.last(),
+                    0 => None,
+                    _ => self.loaded_lines[line].spans.get(span - 1),
+                };
+                if previous.is_some_and(|previous| previous.style.link.as_ref() == Some(link)) {
+                    continue;
+                }
+                links.push((line, span));
+            }
+        }
+        links
+    }
+
+    /// Links of note references on the screen starting at `start_line`.
+    fn visible_notes(&self, start_line: u16, terminal_size: &TermSize) -> Vec<String> {
+        let rows = terminal_size.height - self.config.margin_y * 2;
+        let mut links: Vec<String> = vec![];
+        for line in self
+            .loaded_lines
+            .iter()
+            .skip(start_line.into())
+            .take(rows.into())
+        {
+            for span in &line.spans {
+                if let Some(link) = span.style.link.as_ref().filter(|_| is_note(&span.style)) {
+                    if !links.contains(link) {
+                        links.push(link.clone());
+                    }
+                }
+            }
+        }
+        links
+    }
+
+    /// Link drawn at a screen position and whether it's a note reference.
+    fn link_at(&self, start_line: u16, column: u16, row: u16) -> Option<(String, bool)> {
+        let line = usize::from(start_line) + usize::from(row.checked_sub(self.config.margin_y)?);
+        let column = column.checked_sub(self.config.margin_x)?;
+        let span = self.loaded_lines.get(line)?.span_at(column.into())?;
+        let link = span.style.link.clone()?;
+        Some((link, is_note(&span.style)))
+    }
+
+    /// Link of a span of the loaded lines and whether it's a note reference, notes are
+    /// shown in a popup instead of being followed.
+    fn link_target(&self, line: usize, span: usize) -> Option<(String, bool)> {
+        let style = &self.loaded_lines[line].spans[span].style;
+        let link = style.link.clone()?;
+        Some((link, is_note(style)))
+    }
+
+    /// Lays out the notes `links` from the file `src` point to and shows them.
+    fn show_notes<W: Write>(
+        &mut self,
+        links: &[String],
+        src: &str,
+        screen: &mut W,
+        terminal_size: &TermSize,
+        styler: &dyn Styler,
+    ) {
+        let mut options = self.layout_options(terminal_size);
+        options.max_chars_in_line = options.max_chars_in_line.saturating_sub(NOTE_CHROME);
+        let mut lines = vec![];
+        for link in links {
+            let (file, fragment) = resolve_link(src, link);
+            let Some(fragment) = fragment.filter(|_| Path::new(&file).exists()) else {
+                continue;
+            };
+            let note = HtmlToLine::note_as_lines(&file, &fragment, &options);
+            if !note.is_empty() && !lines.is_empty() {
+                lines.push(Line::new());
+            }
+            lines.extend(note);
+        }
+        if lines.is_empty() {
+            return;
+        }
+        self.note_lines = lines;
+        self.state = ReaderState::NoteShown;
+        self.print_note(screen, terminal_size, styler);
+    }
+
+    /// Draws what the reader is showing in its current state.
+    fn print_state<W: Write>(
+        &self,
+        screen: &mut W,
+        first_line: u16,
+        selected_option: usize,
+        terminal_size: &TermSize,
+        styler: &dyn Styler,
+        toc_styler: &dyn Styler,
+    ) {
+        match self.state {
+            ReaderState::CoverShown => {
+                self.print_cover(screen, terminal_size, styler);
+            }
+            ReaderState::ContentShown => {
+                self.print_section(first_line, screen, terminal_size, styler);
+            }
+            ReaderState::TocShown => {
+                self.print_toc(screen, selected_option, terminal_size, toc_styler);
+            }
+            ReaderState::NoteShown => {
+                self.print_section(first_line, screen, terminal_size, styler);
+                self.print_note(screen, terminal_size, styler);
+            }
+            ReaderState::HintsShown => {
+                self.print_section(first_line, screen, terminal_size, styler);
+                self.print_hints(screen, first_line, styler);
+            }
+            ReaderState::ImageShown => {
+                self.print_image(screen, terminal_size, styler);
+            }
+        }
+    }
+
+    /// Draws labels of the hints matching what was typed over the start of their links.
+    fn print_hints<W: Write>(&self, screen: &mut W, start_line: u16, styler: &dyn Styler) {
+        let style = Style::new("hint");
+        for (label, (line, span)) in &self.hints {
+            let Some(rest) = label.strip_prefix(&self.hint_input) else {
+                continue;
+            };
+            let offset: usize = self.loaded_lines[*line].spans[..*span]
+                .iter()
+                .map(|span| width(&span.text))
+                .sum();
+            let row = self.config.margin_y + (*line - usize::from(start_line)) as u16;
+            let column = self.config.margin_x + offset as u16;
+            let mut hint = Line::new();
+            hint.push(rest, &style);
+            self.term.write_line(screen, row, column, &hint, styler);
+        }
+        screen.flush().unwrap();
+    }
+
+    /// Draws the notes in a box over the middle of the content.
+    fn print_note<W: Write>(&self, screen: &mut W, terminal_size: &TermSize, styler: &dyn Styler) {
+        let box_width = terminal_size
+            .width
+            .saturating_sub(self.config.margin_x.saturating_mul(2));
+        let inner_width = usize::from(box_width.saturating_sub(NOTE_CHROME));
+        let available = terminal_size
+            .height
+            .saturating_sub(self.config.margin_y.saturating_mul(2));
+        let rows = self
+            .note_lines
+            .len()
+            .min(usize::from(available.saturating_sub(2)));
+        let top = self.config.margin_y + available.saturating_sub(rows as u16 + 2) / 2;
+
+        let border = Style::new("note_border");
+        let rule = |left: char, right: char| {
+            let mut line = Line::new();
+            let fill = "─".repeat(inner_width + 2);
+            line.push(&format!("{}{}{}", left, fill, right), &border);
+            line
+        };
+        let column = self.config.margin_x;
+        self.term
+            .write_line(screen, top, column, &rule('┌', '┐'), styler);
+        for i in 0..rows {
+            let mut content = self.note_lines[i].clone();
+            if i + 1 == rows && rows < self.note_lines.len() {
+                content = Line::from("…");
+            }
+            let padding = inner_width.saturating_sub(width(&content.text()));
+            let mut line = Line::new();
+            line.push("│ ", &border);
+            line.spans.extend(content.spans);
+            line.push(&" ".repeat(padding), &Style::default());
+            line.push(" │", &border);
+            self.term
+                .write_line(screen, top + 1 + i as u16, column, &line, styler);
+        }
+        self.term.write_line(
+            screen,
+            top + 1 + rows as u16,
+            column,
+            &rule('└', '┘'),
+            styler,
+        );
+        screen.flush().unwrap();
+    }
+
+    fn print_section<W: Write>(
+        &self,
+        start_line: u16,
+        screen: &mut W,
+        terminal_size: &TermSize,
+        styler: &dyn Styler,
+    ) {
         let end_line = start_line + terminal_size.height - self.config.margin_y * 2;
         let lines_to_print = match end_line as usize >= self.loaded_lines.len() {
             true => &self.loaded_lines[start_line as usize..],
             false => &self.loaded_lines[start_line as usize..end_line as usize],
         };
 
-        let mut row = self.config.margin_y;
+        for ((row, line), index) in (self.config.margin_y..)
+            .zip(lines_to_print)
+            .zip(start_line as usize..)
+        {
+            let line = match self.focused_link {
+                Some((focused, span)) if focused == index => &focus(line, span),
+                _ => line,
+            };
+            self.term
+                .write_line(screen, row, self.config.margin_x, line, styler);
+        }
+        self.draw_images(screen, lines_to_print, self.config.margin_y);
+        screen.flush().unwrap();
+    }
 
-        for line in lines_to_print {
-            self.term.write(screen, row, self.config.margin_x, line);
-            row += 1;
+    /// Draws images over the lines reserved for them from row `top`, cut to the part on
+    /// the screen.
+    fn draw_images<W: Write>(&self, screen: &mut W, lines: &[Line], top: u16) {
+        // Other images are drawn with the text
+        if self.graphics.cell_size().is_none() {
+            return;
+        }
+        for (index, line) in lines.iter().enumerate() {
+            let Some(image) = &line.image else {
+                continue;
+            };
+            // Drawn from its first visible line
+            if index > 0 && image.row > 0 {
+                continue;
+            }
+            let Ok(decoded) = image::open(&image.path) else {
+                continue;
+            };
+            let decoded = decoded.to_rgba8();
+            let shown = (image.rows - image.row).min(lines.len() - index);
+            let height = decoded.height() as usize;
+            let first = height * image.row / image.rows;
+            let last = height * (image.row + shown) / image.rows;
+            let part = imageops::crop_imm(
+                &decoded,
+                0,
+                first as u32,
+                decoded.width(),
+                (last - first).max(1) as u32,
+            )
+            .to_image();
+            self.term.draw_image(
+                screen,
+                top + index as u16,
+                self.config.margin_x + image.column as u16,
+                &part,
+                image.columns as u16,
+                shown as u16,
+            );
         }
+    }
+
+    /// Cover image with the title and author under it, centered on the screen.
+    fn print_cover<W: Write>(&self, screen: &mut W, terminal_size: &TermSize, styler: &dyn Styler) {
+        self.term.clear(screen);
+        let width = usize::from(terminal_size.width.saturating_sub(self.config.margin_x * 2));
+        let height = usize::from(
+            terminal_size
+                .height
+                .saturating_sub(self.config.margin_y * 2),
+        );
+
+        let mut captions = vec![];
+        for (text, key) in [
+            (&self.cover.title, "cover_title"),
+            (&self.cover.author, "cover_author"),
+        ] {
+            if let Some(text) = text {
+                let mut line = Line::new();
+                line.push(text, &Style::new(key));
+                captions.push(align(line, Alignment::Center, width, true));
+            }
+        }
+        let mut lines = vec![];
+        if let Some(path) = &self.cover.image {
+            let picture = Picture {
+                path: path.clone(),
+                alt: "cover".to_owned(),
+            };
+            let rows = height.saturating_sub(captions.len() + 1);
+            picture.layout_fit(width, rows, &self.layout_options(terminal_size), &mut lines);
+            lines.push(Line::new());
+        }
+        lines.extend(captions);
+        lines.truncate(height);
+
+        let top = self.config.margin_y + (height.saturating_sub(lines.len()) / 2) as u16;
+        for (row, line) in (top..).zip(&lines) {
+            self.term
+                .write_line(screen, row, self.config.margin_x, line, styler);
+        }
+        self.draw_images(screen, &lines, top);
+        screen.flush().unwrap();
+    }
+
+    /// Shown image scaled to the whole screen, with a line of help under it.
+    fn print_image<W: Write>(&self, screen: &mut W, terminal_size: &TermSize, styler: &dyn Styler) {
+        let Some(viewer) = &self.viewer else {
+            return;
+        };
+        self.term.clear(screen);
+        let width = usize::from(terminal_size.width);
+        let height = usize::from(terminal_size.height.saturating_sub(1));
+        let view = viewer.view();
+        let cell = match self.graphics {
+            Graphics::None => None,
+            Graphics::HalfBlocks => Some(HALF_BLOCK_CELL),
+            Graphics::Kitty(cell) | Graphics::Sixel(cell) => Some(cell),
+        };
+        if let Some(cell) = cell {
+            let (columns, rows) = fill(view.dimensions(), cell, width, height);
+            let top = ((height - rows) / 2) as u16;
+            let left = ((width - columns) / 2) as u16;
+            match self.graphics {
+                Graphics::HalfBlocks => {
+                    let scaled = imageops::resize(
+                        &view,
+                        columns as u32,
+                        rows as u32 * 2,
+                        FilterType::Triangle,
+                    );
+                    for (row, line) in (top..).zip(half_blocks(&scaled)) {
+                        self.term.write_line(screen, row, left, &line, styler);
+                    }
+                }
+                _ => self
+                    .term
+                    .draw_image(screen, top, left, &view, columns as u16, rows as u16),
+            }
+        }
+
+        let keys = self.config.keys;
+        let help = match cell {
+            Some(_) => format!(
+                "×{}  {} {} zoom  {} open  {} close",
+                viewer.zoom(),
+                keys.zoom_in,
+                keys.zoom_out,
+                keys.open_image,
+                keys.back
+            ),
+            None => format!(
+                "Images can't be shown here  {} open  {} close",
+                keys.open_image, keys.back
+            ),
+        };
+        let mut line = Line::new();
+        line.push(&help, &Style::new("image_alt"));
+        let line = align(line, Alignment::Center, width, true);
+        self.term.write_line(
+            screen,
+            terminal_size.height.saturating_sub(1),
+            0,
+            &line,
+            styler,
+        );
         screen.flush().unwrap();
     }
 
@@ -378,23 +1189,59 @@ impl<'a> EpubReader<'a> {
     ) {
         self.term.clear(screen);
         for (i, e) in self.toc.iter().enumerate() {
-            let start_cell = (usize::from(terminal_size.width) / 2) - (e.text.len() / 2);
-            if i == selected_option {
-                self.term.write(
-                    screen,
-                    (i + 2).try_into().unwrap(),
-                    start_cell.try_into().unwrap(),
-                    &styler.style(&e.text, "selected"),
-                )
-            } else {
-                self.term.write(
-                    screen,
-                    (i + 2).try_into().unwrap(),
-                    start_cell.try_into().unwrap(),
-                    &styler.style(&e.text, "not_selected"),
-                )
-            }
+            let start_cell =
+                (usize::from(terminal_size.width) / 2).saturating_sub(width(&e.text) / 2);
+            let key = match i == selected_option {
+                true => "selected",
+                false => "not_selected",
+            };
+            let mut line = Line::new();
+            line.push(&e.text, &Style::new(key));
+            self.term.write_line(
+                screen,
+                (i + 2).try_into().unwrap(),
+                start_cell.try_into().unwrap(),
+                &line,
+                styler,
+            );
         }
         screen.flush().unwrap();
     }
 }
+
+/// Runs `command` with `argument` appended, without waiting for it. It's reaped in the
+/// background once it exits.
+fn spawn(command: &str, argument: &str) {
+    let mut words = command.split_whitespace();
+    let Some(program) = words.next() else {
+        return;
+    };
+    // Failing to open something shouldn't close the book
+    let child = Command::new(program)
+        .args(words)
+        .arg(argument)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Ok(mut child) = child {
+        thread::spawn(move || child.wait());
+    }
+}
+
+fn is_note(style: &Style) -> bool {
+    style.keys.iter().any(|key| key == "noteref")
+}
+
+/// Line with the link starting at `span` highlighted.
+fn focus(line: &Line, span: usize) -> Line {
+    let mut focused = line.clone();
+    let link = line.spans[span].style.link.clone();
+    for span in focused.spans[span..]
+        .iter_mut()
+        .take_while(|other| other.style.link == link)
+    {
+        span.style.keys.push("focused_link".to_owned());
+    }
+    focused
+}
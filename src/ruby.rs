@@ -0,0 +1,78 @@
+use crate::line::{Line, Style};
+use crate::wrap::width;
+
+/// How ruby annotations, like furigana over Japanese text, are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RubyLayout {
+    /// In parentheses after the base text, like `漢字(かんじ)`.
+    Inline,
+    /// On a line of their own above the line with the base text, centered over it.
+    Above,
+    Hidden,
+}
+
+/// Base text padded with non-breaking spaces to be as wide as its annotation, so the
+/// annotation fits over it.
+pub fn pad_base(base_width: usize, annotation: &str) -> (String, String) {
+    let free = width(annotation).saturating_sub(base_width);
+    let left = free / 2;
+    ("\u{A0}".repeat(left), "\u{A0}".repeat(free - left))
+}
+
+/// Line of annotations to draw above `line`, each centered over the spans of its base.
+pub fn annotations(line: &Line) -> Option<Line> {
+    if line.spans.iter().all(|span| span.style.ruby.is_none()) {
+        return None;
+    }
+    let style = Style::new("rt");
+    let mut annotations = Line::new();
+    let mut used = 0;
+    let mut column = 0;
+    let mut spans = line.spans.iter().peekable();
+    while let Some(span) = spans.next() {
+        let mut base_width = width(&span.text);
+        let Some(annotation) = &span.style.ruby else {
+            column += base_width;
+            continue;
+        };
+        // A base with styled parts is split into spans with the same annotation
+        while let Some(next) = spans.next_if(|next| next.style.ruby.as_ref() == Some(annotation)) {
+            base_width += width(&next.text);
+        }
+        // Annotations which would overlap the one before are moved right
+        let start = (column + base_width.saturating_sub(width(annotation)) / 2).max(used);
+        annotations.push(&" ".repeat(start - used), &Style::default());
+        annotations.push(annotation, &style);
+        used = start + width(annotation);
+        column += base_width;
+    }
+    Some(annotations)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line::{Line, Style};
+    use crate::ruby::{annotations, pad_base};
+
+    #[test]
+    fn annotations_are_centered_over_their_base() {
+        let ruby = |annotation: &str| Style {
+            ruby: Some(annotation.to_owned()),
+            ..Style::default()
+        };
+        let mut line = Line::new();
+        line.push("私は", &Style::default());
+        line.push(
+            "\u{A0}\u{A0}\u{A0}東京\u{A0}\u{A0}\u{A0}",
+            &ruby("とうきょう"),
+        );
+        line.push("へ", &Style::default());
+        line.push("行", &ruby("い"));
+        line.push("く", &Style::default());
+        assert_eq!(annotations(&line).unwrap().text(), "    とうきょう  い");
+        assert_eq!(annotations(&Line::from("私は")), None);
+
+        let (left, right) = pad_base(4, "とうきょう");
+        assert_eq!((left.chars().count(), right.chars().count()), (3, 3));
+    }
+}
@@ -1,11 +1,14 @@
 mod tag_styler;
+mod theme;
 mod toc_styler;
 
+use crossterm::style::ContentStyle;
 pub use tag_styler::TagStyler;
+pub use theme::Theme;
 pub use toc_styler::TocStyler;
 
 pub trait Styler {
-    fn style(&self, text: &str, key: &str) -> String;
+    fn style(&self, key: &str) -> ContentStyle;
 }
 
 pub struct EmptyStyler;
@@ -15,7 +18,7 @@ impl EmptyStyler {
     }
 }
 impl Styler for EmptyStyler {
-    fn style(&self, text: &str, key: &str) -> std::string::String {
-        text.to_owned()
+    fn style(&self, _key: &str) -> ContentStyle {
+        ContentStyle::new()
     }
 }
@@ -1,30 +1,37 @@
+use crate::styler::theme::{color, Theme};
 use crate::styler::Styler;
-use crossterm::style::Stylize;
+use crossterm::style::{ContentStyle, Stylize};
 
-pub struct TagStyler {}
+/// Styles of the book's content, from the theme and the book's CSS.
+pub struct TagStyler {
+    theme: Theme,
+}
 
 impl TagStyler {
-    pub fn new() -> Self {
-        TagStyler {}
+    pub fn new(theme: &Theme) -> Self {
+        TagStyler {
+            theme: theme.clone(),
+        }
     }
 }
 impl Styler for TagStyler {
-    fn style(&self, text: &str, key: &str) -> String {
-        let new_line = " NEW_LINE ";
-        let formated_text = match key {
-            "a" => text.blue().underline(crossterm::style::Color::Blue),
-            "p" | "div" => text.red(),
-            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => text.green().bold(),
-            "b" => text.white().bold(),
-            "em" => text.white().italic(),
-            "li" => text.yellow(),
-            "dt" | "dd" | "blockquote" | "q" => text.green().italic(),
-            "span" => text.white().on_blue().bold(),
-            "i" => text.white().italic(),
-            "body" | "script" | "head" | "link" | "!DOCTYPE" | "html" | "?xml" => "".white(),
-            _ => text.white(),
-        };
-
-        formated_text.to_string()
+    fn style(&self, key: &str) -> ContentStyle {
+        let style = ContentStyle::new();
+        match key {
+            // Set by the book's CSS
+            "bold" => style.bold(),
+            "italic" => style.italic(),
+            "underline" => style.underlined(),
+            "line_through" => style.crossed_out(),
+            _ if key.starts_with("color:") => match color(&key["color:".len()..]) {
+                Some(color) => style.with(color),
+                None => style,
+            },
+            _ if key.starts_with("background:") => match color(&key["background:".len()..]) {
+                Some(color) => style.on(color),
+                None => style,
+            },
+            _ => self.theme.style(key),
+        }
     }
 }
@@ -0,0 +1,215 @@
+use crate::styler::Styler;
+use crossterm::style::{Attribute, Color, ContentStyle};
+use std::collections::HashMap;
+
+/// Themes every reader has, a theme file can add more or replace them.
+pub const BUILTIN_THEMES: &str = "
+[dark]
+p div = red
+h1 h2 h3 h4 h5 h6 = green bold
+a = blue underline
+b th cover_title = white bold
+em i = white italic
+li list_marker noteref = yellow
+dt dd blockquote q = green italic
+span = white on blue bold
+code kbd samp tt math = cyan
+pre rt = grey
+code_keyword = magenta bold
+code_string = green
+code_number = yellow
+code_comment = dark_grey italic
+pre_overflow image_alt table_border note_border = dark_grey
+cover_author = grey italic
+focused_link = black on yellow
+hint = black on yellow bold
+toc = white bold
+toc_selected = black on blue bold
+
+[light]
+h1 h2 h3 h4 h5 h6 = dark_blue bold
+a = dark_blue underline
+b th cover_title = bold
+em i dt dd blockquote q = italic
+li list_marker noteref = dark_magenta
+code kbd samp tt math code_keyword = dark_cyan
+code_string = dark_green
+code_number = dark_red
+code_comment pre_overflow image_alt table_border note_border rt = dark_grey
+cover_author = dark_grey italic
+focused_link = black on yellow
+hint = white on dark_blue bold
+toc = black
+toc_selected = white on dark_blue bold
+
+[sepia]
+p div li dt dd pre = #5b4636 on #f4ecd8
+h1 h2 h3 h4 h5 h6 = #704214 on #f4ecd8 bold
+a = #8b4513 underline
+b th cover_title = #3e2c1c bold
+em i blockquote q = italic
+list_marker noteref = #8b4513
+code kbd samp tt math code_keyword = #6b4e16
+code_comment pre_overflow image_alt table_border note_border rt cover_author = #a08a70
+focused_link = #f4ecd8 on #8b4513
+hint = #f4ecd8 on #704214 bold
+toc = #5b4636 on #f4ecd8
+toc_selected = #f4ecd8 on #704214 bold
+
+[high-contrast]
+p div li dt dd pre code kbd samp tt math = white on black
+h1 h2 h3 h4 h5 h6 b th cover_title = white on black bold
+a = yellow on black underline bold
+em i blockquote q = white on black italic
+list_marker noteref rt image_alt table_border note_border = yellow on black
+focused_link hint = black on yellow bold
+toc = white on black bold
+toc_selected = black on white bold
+";
+
+/// Colours and attributes of element names, `.class`es of the book and roles of the
+/// interface, like `toc_selected`, `focused_link` or `hint`. Themes are written as:
+///
+/// ```text
+/// [name]
+/// h1 h2 .title = green on #202020 bold
+/// ```
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    styles: HashMap<String, ContentStyle>,
+}
+
+impl Theme {
+    /// Themes of a theme file in the order they're written. Lines starting with `#` and
+    /// words which aren't colours or attributes are skipped.
+    pub fn parse(text: &str) -> Vec<Theme> {
+        let mut themes: Vec<Theme> = vec![];
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                themes.push(Theme {
+                    name: name.trim().to_owned(),
+                    styles: HashMap::new(),
+                });
+                continue;
+            }
+            let (Some(theme), Some((keys, value))) = (themes.last_mut(), line.split_once('='))
+            else {
+                continue;
+            };
+            let style = parse_style(value);
+            for key in keys.split_whitespace() {
+                theme.styles.insert(key.to_owned(), style);
+            }
+        }
+        themes
+    }
+
+    /// Built-in themes with those of a theme file added, a theme of the file replaces the
+    /// built-in one with the same name.
+    pub fn all(file: Option<&str>) -> Vec<Theme> {
+        let mut themes = Theme::parse(BUILTIN_THEMES);
+        for theme in Theme::parse(file.unwrap_or("")) {
+            match themes.iter_mut().find(|builtin| builtin.name == theme.name) {
+                Some(builtin) => *builtin = theme,
+                None => themes.push(theme),
+            }
+        }
+        themes
+    }
+
+    /// Classes styled by the theme, without the dot.
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        self.styles.keys().filter_map(|key| key.strip_prefix('.'))
+    }
+}
+
+impl Styler for Theme {
+    fn style(&self, key: &str) -> ContentStyle {
+        self.styles.get(key).copied().unwrap_or_default()
+    }
+}
+
+/// Style from words like `white on blue bold`, the colour after `on` is the background.
+fn parse_style(value: &str) -> ContentStyle {
+    let mut style = ContentStyle::new();
+    let mut background = false;
+    for word in value.split_whitespace() {
+        match word {
+            "on" => background = true,
+            "bold" => style.attributes.set(Attribute::Bold),
+            "dim" => style.attributes.set(Attribute::Dim),
+            "italic" => style.attributes.set(Attribute::Italic),
+            "underline" => style.attributes.set(Attribute::Underlined),
+            "reverse" => style.attributes.set(Attribute::Reverse),
+            "line_through" => style.attributes.set(Attribute::CrossedOut),
+            _ => match (color(word), background) {
+                (Some(color), true) => {
+                    style.background_color = Some(color);
+                    background = false;
+                }
+                (Some(color), false) => style.foreground_color = Some(color),
+                (None, _) => {}
+            },
+        }
+    }
+    style
+}
+
+/// Parses a colour name, like `dark_grey`, or a `#rrggbb` colour.
+pub fn color(name: &str) -> Option<Color> {
+    let Some(hex) = name.strip_prefix('#') else {
+        return Color::try_from(name).ok();
+    };
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color::Rgb {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::styler::theme::Theme;
+    use crate::styler::Styler;
+    use crossterm::style::{Attribute, Color, ContentStyle};
+
+    #[test]
+    fn themes_map_keys_to_colours_and_attributes() {
+        let file = "# Mine
+            [dark]
+            p .poem = #102030 on white italic
+            [paper]
+            toc_selected = reverse";
+        let themes = Theme::all(Some(file));
+        let names: Vec<_> = themes.iter().map(|theme| theme.name.as_str()).collect();
+        assert_eq!(names, ["dark", "light", "sepia", "high-contrast", "paper"]);
+
+        let dark = &themes[0];
+        let style = dark.style(".poem");
+        assert_eq!(
+            style.foreground_color,
+            Some(Color::Rgb {
+                r: 0x10,
+                g: 0x20,
+                b: 0x30
+            })
+        );
+        assert_eq!(style.background_color, Some(Color::White));
+        assert!(style.attributes.has(Attribute::Italic));
+        assert_eq!(dark.classes().collect::<Vec<_>>(), ["poem"]);
+        // The file's theme replaced the built-in one
+        assert_eq!(dark.style("h1"), ContentStyle::new());
+        assert!(themes[4]
+            .style("toc_selected")
+            .attributes
+            .has(Attribute::Reverse));
+    }
+}
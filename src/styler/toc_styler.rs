@@ -1,19 +1,23 @@
-use crossterm::style::Stylize;
+use crossterm::style::ContentStyle;
 
+use crate::styler::theme::Theme;
 use crate::styler::Styler;
 
-pub struct TocStyler {}
+pub struct TocStyler {
+    theme: Theme,
+}
 impl TocStyler {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(theme: &Theme) -> Self {
+        Self {
+            theme: theme.clone(),
+        }
     }
 }
 impl Styler for TocStyler {
-    fn style(&self, text: &str, key: &str) -> String {
-        let formated_text = match key {
-            "selected" => text.black().on_blue(),
-            _ => text.white(),
-        };
-        formated_text.bold().to_string()
+    fn style(&self, key: &str) -> ContentStyle {
+        match key {
+            "selected" => self.theme.style("toc_selected"),
+            _ => self.theme.style("toc"),
+        }
     }
 }
@@ -0,0 +1,276 @@
+use crate::dom::{Document, NodeId};
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{fontdb, Options, Tree};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// SVG names with capitals, the parser lowercases all names.
+const CAMEL_CASE: [&str; 38] = [
+    "attributeName",
+    "baseFrequency",
+    "clipPath",
+    "clipPathUnits",
+    "feBlend",
+    "feColorMatrix",
+    "feComposite",
+    "feFlood",
+    "feGaussianBlur",
+    "feMerge",
+    "feMergeNode",
+    "feOffset",
+    "foreignObject",
+    "gradientTransform",
+    "gradientUnits",
+    "kernelMatrix",
+    "lengthAdjust",
+    "linearGradient",
+    "markerHeight",
+    "markerUnits",
+    "markerWidth",
+    "maskContentUnits",
+    "maskUnits",
+    "pathLength",
+    "patternContentUnits",
+    "patternTransform",
+    "patternUnits",
+    "preserveAspectRatio",
+    "primitiveUnits",
+    "radialGradient",
+    "refX",
+    "refY",
+    "spreadMethod",
+    "startOffset",
+    "stdDeviation",
+    "textLength",
+    "textPath",
+    "viewBox",
+];
+
+/// What an `svg` element says in words: its title and description, then its texts in
+/// reading order, top to bottom and left to right.
+pub fn text(doc: &Document, id: NodeId) -> (Option<String>, Vec<String>) {
+    let descendants = doc.descendants(id);
+    let words = |name: &str| {
+        descendants
+            .iter()
+            .find(|child| doc.name(**child) == Some(name))
+            .map(|child| collapse(&doc.text_content(*child)))
+            .filter(|text| !text.is_empty())
+    };
+    let label = match (words("title"), words("desc")) {
+        (Some(title), Some(desc)) => Some(format!("{}: {}", title, desc)),
+        (title, desc) => title.or(desc),
+    };
+
+    let mut texts: Vec<((f32, f32), String)> = descendants
+        .iter()
+        .filter(|child| doc.name(**child) == Some("text"))
+        .map(|text| (position(doc, *text), collapse(&doc.text_content(*text))))
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+    texts.sort_by(|(a, _), (b, _)| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+    (label, texts.into_iter().map(|(_, text)| text).collect())
+}
+
+/// Where a `text` element starts, from its own or its first `tspan`'s coordinates.
+fn position(doc: &Document, text: NodeId) -> (f32, f32) {
+    let coordinate = |name: &str| {
+        doc.descendants(text)
+            .into_iter()
+            .find_map(|id| doc.attr(id, name))
+            .and_then(|value| value.split([' ', ',']).next()?.parse().ok())
+            .unwrap_or(0.0)
+    };
+    (coordinate("x"), coordinate("y"))
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Source of an `svg` element of the document, with the namespaces it needs declared.
+pub fn source(doc: &Document, id: NodeId) -> String {
+    let mut out = String::new();
+    write_element(doc, id, &mut out);
+    out
+}
+
+fn write_element(doc: &Document, id: NodeId, out: &mut String) {
+    if let Some(text) = doc.text(id) {
+        out.push_str(&encode_text(text));
+        return;
+    }
+    let Some(name) = doc.name(id) else {
+        return;
+    };
+    let name = camel_case(name);
+    out.push('<');
+    out.push_str(name);
+    let attrs = doc.attrs(id);
+    if name == "svg" {
+        for (attr, namespace) in [
+            ("xmlns", "http://www.w3.org/2000/svg"),
+            ("xmlns:xlink", "http://www.w3.org/1999/xlink"),
+        ] {
+            if !attrs.iter().any(|(key, _)| key == attr) {
+                out.push_str(&format!(" {}=\"{}\"", attr, namespace));
+            }
+        }
+    }
+    for (key, value) in attrs {
+        out.push_str(&format!(
+            " {}=\"{}\"",
+            camel_case(key),
+            encode_double_quoted_attribute(value)
+        ));
+    }
+    out.push('>');
+    for child in doc.children(id) {
+        write_element(doc, *child, out);
+    }
+    out.push_str(&format!("</{}>", name));
+}
+
+fn camel_case(name: &str) -> &str {
+    CAMEL_CASE
+        .iter()
+        .find(|camel| camel.eq_ignore_ascii_case(name))
+        .copied()
+        .unwrap_or(name)
+}
+
+/// Fonts of the system, loaded once as it takes a while.
+fn fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+/// Draws an SVG into a PNG file at `path`. Files the SVG refers to are looked up next
+/// to the file at `next_to`.
+pub fn rasterize(source: &str, next_to: &Path, path: &str) -> Option<String> {
+    let options = Options {
+        resources_dir: next_to.parent().map(Path::to_path_buf),
+        fontdb: fonts(),
+        ..Options::default()
+    };
+    let tree = Tree::from_str(source, &options).ok()?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = Pixmap::new(size.width(), size.height())?;
+    resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+    pixmap.save_png(path).ok()?;
+    Some(path.to_owned())
+}
+
+/// PNG drawn from an SVG file.
+pub fn rasterize_file(svg_path: &str) -> Option<String> {
+    drawn(svg_path, Path::new(svg_path), || {
+        fs::read_to_string(svg_path).ok()
+    })
+}
+
+/// PNG drawn from an `svg` element of the HTML file at `html_path`.
+pub fn rasterize_element(doc: &Document, id: NodeId, html_path: &str) -> Option<String> {
+    let key = format!("{}#{}", html_path, id);
+    drawn(&key, Path::new(html_path), || Some(source(doc, id)))
+}
+
+/// PNG of the SVG named `key`, drawn the first time it's asked for. Layouts happen on
+/// every resize, so later ones get the PNG drawn before, and PNGs are kept in the cache
+/// directory of the user, named after the SHA-256 of the source, so books are left
+/// untouched.
+fn drawn(key: &str, next_to: &Path, source: impl FnOnce() -> Option<String>) -> Option<String> {
+    static DRAWN: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+    let drawn = DRAWN.get_or_init(Mutex::default);
+    if let Some(png) = drawn.lock().unwrap().get(key) {
+        return png.clone();
+    }
+
+    let png = source().and_then(|source| {
+        // Resources are looked up next to the file, so its directory is hashed too
+        let mut hasher = Sha256::new();
+        let parent = next_to.parent().unwrap_or(Path::new(""));
+        hasher.update(parent.as_os_str().as_encoded_bytes());
+        hasher.update([0]);
+        hasher.update(source.as_bytes());
+        let directory = cache_dir()?;
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&directory)
+            .ok()?;
+        let path = directory.join(format!("{:x}.png", hasher.finalize()));
+        let path = path.to_str()?;
+        match Path::new(path).exists() {
+            true => Some(path.to_owned()),
+            false => rasterize(&source, next_to, path),
+        }
+    });
+    drawn.lock().unwrap().insert(key.to_owned(), png.clone());
+    png
+}
+
+/// `papcio/svg` in `$XDG_CACHE_HOME` or `~/.cache`.
+fn cache_dir() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_dir.join("papcio").join("svg"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::Document;
+    use crate::svg::{rasterize, source, text};
+    use std::path::Path;
+
+    #[test]
+    fn svg_is_drawn_into_a_png() {
+        let path = std::env::temp_dir().join("papcio_rasterized.png");
+        let _ = std::fs::remove_file(&path);
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="8">
+            <rect width="8" height="8" fill="red"/></svg>"#;
+        let png = rasterize(svg, Path::new(""), path.to_str().unwrap()).unwrap();
+
+        let image = image::open(&png).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (16, 8));
+        assert_eq!(image.get_pixel(2, 2).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(12, 2).0[3], 0);
+    }
+
+    #[test]
+    fn texts_are_read_top_to_bottom() {
+        let doc = Document::parse(
+            r#"<svg viewBox="0 0 10 10"><title>Chart</title><desc>Sales</desc>
+            <text x="5" y="20">second <tspan>line</tspan></text>
+            <text><tspan x="1" y="10">first</tspan></text>
+            <linearGradient gradientUnits="userSpaceOnUse"/></svg>"#,
+        );
+        let svg = doc.find_element("svg").unwrap();
+        assert_eq!(
+            text(&doc, svg),
+            (
+                Some("Chart: Sales".to_owned()),
+                vec!["first".to_owned(), "second line".to_owned()]
+            )
+        );
+        let source = source(&doc, svg);
+        assert!(source.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 10 10">"#
+        ));
+        assert!(
+            source.contains(r#"<linearGradient gradientUnits="userSpaceOnUse"></linearGradient>"#)
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::html::LayoutOptions;
+use crate::layout::{blocks_width, layout, Block};
+use crate::line::{Line, Style};
+use crate::wrap::{width, wrap};
+
+pub struct Cell {
+    pub blocks: Vec<Block>,
+    pub colspan: usize,
+    pub header: bool,
+}
+
+pub struct Row {
+    pub cells: Vec<Cell>,
+}
+
+impl Row {
+    fn is_header(&self) -> bool {
+        !self.cells.is_empty() && self.cells.iter().all(|cell| cell.header)
+    }
+}
+
+/// Table drawn with box-drawing borders, or as one record per row when its columns
+/// can't fit in the available width.
+pub struct Table {
+    pub rows: Vec<Row>,
+}
+
+/// Cells around every column: a border and a space on both sides.
+const COLUMN_CHROME: usize = 3;
+
+impl Table {
+    fn columns(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|row| row.cells.iter().map(|cell| cell.colspan).sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Minimum and maximum content width of every column. Spanning cells only widen
+    /// their last column when the columns they cover are too narrow.
+    fn column_widths(&self) -> (Vec<usize>, Vec<usize>) {
+        let columns = self.columns();
+        let mut min = vec![1; columns];
+        let mut max = vec![1; columns];
+        let mut spanning = vec![];
+
+        for row in &self.rows {
+            let mut column = 0;
+            for cell in &row.cells {
+                let cell_min = blocks_width(&cell.blocks, Block::min_width);
+                let cell_max = blocks_width(&cell.blocks, Block::max_width);
+                if cell.colspan == 1 {
+                    min[column] = min[column].max(cell_min);
+                    max[column] = max[column].max(cell_max);
+                } else {
+                    spanning.push((column, cell.colspan, cell_min, cell_max));
+                }
+                column += cell.colspan;
+            }
+        }
+
+        for (column, colspan, cell_min, cell_max) in spanning {
+            let covered = column..column + colspan;
+            let chrome = COLUMN_CHROME * (colspan - 1);
+            let last = column + colspan - 1;
+            let covered_min: usize = min[covered.clone()].iter().sum::<usize>() + chrome;
+            let covered_max: usize = max[covered].iter().sum::<usize>() + chrome;
+            min[last] += cell_min.saturating_sub(covered_min);
+            max[last] += cell_max.saturating_sub(covered_max);
+        }
+        // A spanning cell can widen the minimum past the maximum
+        for (min, max) in min.iter().zip(max.iter_mut()) {
+            *max = (*max).max(*min);
+        }
+        (min, max)
+    }
+
+    pub fn min_width(&self) -> usize {
+        let (min, _) = self.column_widths();
+        min.iter().sum::<usize>() + COLUMN_CHROME * min.len() + 1
+    }
+
+    pub fn max_width(&self) -> usize {
+        let (_, max) = self.column_widths();
+        max.iter().sum::<usize>() + COLUMN_CHROME * max.len() + 1
+    }
+
+    pub fn layout(&self, max_width: usize, options: &LayoutOptions, out: &mut Vec<Line>) {
+        let columns = self.columns();
+        if columns == 0 {
+            return;
+        }
+        let available = max_width.saturating_sub(COLUMN_CHROME * columns + 1);
+        let (min, max) = self.column_widths();
+
+        let min_total: usize = min.iter().sum();
+        let max_total: usize = max.iter().sum();
+        if min_total > available {
+            self.layout_records(max_width, options, out);
+            return;
+        }
+
+        let widths = match max_total <= available {
+            true => max,
+            false => {
+                // Give every column its minimum and share what's left by how much
+                // more each column would like to have
+                let extra = available - min_total;
+                let wanted = max_total.saturating_sub(min_total);
+                let mut widths: Vec<usize> = min
+                    .iter()
+                    .zip(&max)
+                    .map(|(min, max)| min + (max - min) * extra / wanted)
+                    .collect();
+                let mut left = available - widths.iter().sum::<usize>();
+                for (width, max) in widths.iter_mut().zip(&max) {
+                    if left > 0 && *width < *max {
+                        *width += 1;
+                        left -= 1;
+                    }
+                }
+                widths
+            }
+        };
+
+        let border = Style::new("table_border");
+        let boundaries: Vec<Vec<usize>> = self
+            .rows
+            .iter()
+            .map(|row| row_boundaries(row, columns))
+            .collect();
+
+        out.push(rule(&widths, None, boundaries.first(), '─', &border));
+        for (i, row) in self.rows.iter().enumerate() {
+            self.layout_row(row, &widths, options, out);
+            let below = boundaries.get(i + 1);
+            if below.is_some() {
+                let fill = match row.is_header() {
+                    true => '═',
+                    false => '─',
+                };
+                out.push(rule(&widths, Some(&boundaries[i]), below, fill, &border));
+            }
+        }
+        out.push(rule(&widths, boundaries.last(), None, '─', &border));
+    }
+
+    fn layout_row(
+        &self,
+        row: &Row,
+        widths: &[usize],
+        options: &LayoutOptions,
+        out: &mut Vec<Line>,
+    ) {
+        let border = Style::new("table_border");
+        let mut column = 0;
+        let mut cells = vec![];
+        for cell in &row.cells {
+            let span = column..(column + cell.colspan).min(widths.len());
+            let cell_width =
+                widths[span.clone()].iter().sum::<usize>() + COLUMN_CHROME * (span.len() - 1);
+            let mut lines = vec![];
+            layout(&cell.blocks, cell_width, 0, options, &mut lines);
+            cells.push((cell_width, lines));
+            column += cell.colspan;
+        }
+        // Rows with missing cells still get empty ones so the borders line up
+        while column < widths.len() {
+            cells.push((widths[column], vec![]));
+            column += 1;
+        }
+
+        let height = cells
+            .iter()
+            .map(|(_, lines)| lines.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        for i in 0..height {
+            let mut line = Line::new();
+            line.push("│", &border);
+            for (cell_width, lines) in &cells {
+                line.push(" ", &Style::default());
+                let content = lines.get(i).cloned().unwrap_or_default();
+                let padding = cell_width.saturating_sub(width(&content.text()));
+                line.spans.extend(content.spans);
+                line.push(&" ".repeat(padding + 1), &Style::default());
+                line.push("│", &border);
+            }
+            out.push(line);
+        }
+    }
+
+    /// Every body row becomes a list of `header: value` lines.
+    fn layout_records(&self, max_width: usize, options: &LayoutOptions, out: &mut Vec<Line>) {
+        let headers: Vec<String> = match self.rows.iter().find(|row| row.is_header()) {
+            Some(row) => row
+                .cells
+                .iter()
+                .flat_map(|cell| {
+                    let text = blocks_text(&cell.blocks);
+                    std::iter::repeat_n(text, cell.colspan)
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        let label = Style::new("th");
+        let mut first = true;
+        for row in self.rows.iter().filter(|row| !row.is_header()) {
+            if !first {
+                out.push(Line::new());
+            }
+            first = false;
+
+            let mut column = 0;
+            for cell in &row.cells {
+                let mut record = Line::new();
+                if let Some(header) = headers.get(column).filter(|header| !header.is_empty()) {
+                    record.push(&format!("{}: ", header), &label);
+                }
+                for block in &cell.blocks {
+                    if let Block::Paragraph(paragraph) = block {
+                        if !record.is_empty() && !record.text().ends_with(' ') {
+                            record.push(" ", &Style::default());
+                        }
+                        record.spans.extend(paragraph.line.spans.iter().cloned());
+                    }
+                }
+                out.extend(wrap(&record, max_width, options.hyphenation));
+                column += cell.colspan;
+            }
+        }
+    }
+}
+
+fn blocks_text(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Paragraph(paragraph) => Some(paragraph.line.text()),
+            Block::Preformatted(preformatted) => Some(
+                preformatted
+                    .lines
+                    .iter()
+                    .map(|line| line.text())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Indices of columns which start a new cell, the first column excluded. Missing cells
+/// at the end of the row are drawn as empty ones.
+fn row_boundaries(row: &Row, columns: usize) -> Vec<usize> {
+    let mut boundaries = vec![];
+    let mut column = 0;
+    for cell in &row.cells {
+        if column > 0 {
+            boundaries.push(column);
+        }
+        column += cell.colspan;
+    }
+    boundaries.extend(column.max(1)..columns);
+    boundaries
+}
+
+/// Horizontal border, junctions depend on cell boundaries in the rows above and below.
+fn rule(
+    widths: &[usize],
+    above: Option<&Vec<usize>>,
+    below: Option<&Vec<usize>>,
+    fill: char,
+    style: &Style,
+) -> Line {
+    let double = fill == '═';
+    let (left, right) = match (above.is_some(), below.is_some(), double) {
+        (false, _, _) => ('┌', '┐'),
+        (_, false, _) => ('└', '┘'),
+        (_, _, true) => ('╞', '╡'),
+        _ => ('├', '┤'),
+    };
+
+    let mut text = String::new();
+    text.push(left);
+    for (column, column_width) in widths.iter().enumerate() {
+        if column > 0 {
+            let up = above.is_some_and(|boundaries| boundaries.contains(&column));
+            let down = below.is_some_and(|boundaries| boundaries.contains(&column));
+            text.push(match (up, down, double) {
+                (true, true, true) => '╪',
+                (true, false, true) => '╧',
+                (false, true, true) => '╤',
+                (true, true, false) => '┼',
+                (true, false, false) => '┴',
+                (false, true, false) => '┬',
+                (false, false, _) => fill,
+            });
+        }
+        text.extend(std::iter::repeat_n(fill, column_width + 2));
+    }
+    text.push(right);
+
+    let mut line = Line::new();
+    line.push(&text, style);
+    line
+}
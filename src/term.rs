@@ -1,45 +1,158 @@
-use std::io::{stdin, stdout, Error, Write};
+use crate::graphics::{self, kitty, sixel, Graphics, KITTY_CLEAR};
+use crate::html::is_external;
+use crate::line::{Line, Style};
+use crate::styler::Styler;
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use std::io::Write;
 use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, Once};
 use std::thread;
 
-use crossterm::event::{read, Event, KeyCode};
-use crossterm::style::Stylize;
-use crossterm::style::{self};
+use crossterm::event::{read, Event, KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::style::{self, ContentStyle};
 use crossterm::terminal::{size, Clear, ClearType};
-use crossterm::{QueueableCommand, ExecutableCommand};
 use crossterm::{cursor::MoveTo, Result};
+use crossterm::{ExecutableCommand, QueueableCommand};
 
+#[derive(Clone, Copy)]
 pub struct TermSize {
     pub width: u16,
     pub height: u16,
 }
 
 pub trait Terminal {
-    fn write(&self, screen: &mut dyn Write, row: u16, column: u16, content: &str);
+    fn write_line(
+        &self,
+        screen: &mut dyn Write,
+        row: u16,
+        column: u16,
+        line: &Line,
+        styler: &dyn Styler,
+    );
     fn clear(&self, screen: &mut dyn Write);
     fn get_size(&self) -> Result<TermSize>;
     fn on_input(&self) -> Result<Receiver<char>>;
     fn on_resize(&self) -> Result<Receiver<TermSize>>;
+    /// Column and row of every left click, mouse capture has to be enabled.
+    fn on_click(&self) -> Result<Receiver<(u16, u16)>>;
+    /// How images can be drawn, asking the terminal the first time. Raw mode has to be
+    /// enabled and no events read yet.
+    fn graphics(&mut self) -> Graphics;
+    /// Draws the image scaled over `columns` by `rows` cells, when the terminal draws
+    /// pixels itself.
+    fn draw_image(
+        &self,
+        screen: &mut dyn Write,
+        row: u16,
+        column: u16,
+        image: &RgbaImage,
+        columns: u16,
+        rows: u16,
+    );
 }
 
-pub struct TermionTerminal;
+/// Channels terminal events are sent to.
+#[derive(Default)]
+struct Listeners {
+    keys: Vec<Sender<char>>,
+    resizes: Vec<Sender<TermSize>>,
+    clicks: Vec<Sender<(u16, u16)>>,
+}
+
+pub struct TermionTerminal {
+    listeners: Arc<Mutex<Listeners>>,
+    reading: Once,
+    /// Whether external links are written as OSC 8 hyperlinks.
+    hyperlinks: bool,
+    /// Way of drawing images, detected when not set.
+    graphics: Option<Graphics>,
+}
 
 impl TermionTerminal {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(hyperlinks: bool, graphics: Option<Graphics>) -> Self {
+        Self {
+            listeners: Arc::new(Mutex::new(Listeners::default())),
+            reading: Once::new(),
+            hyperlinks,
+            graphics,
+        }
+    }
+
+    /// Starts the thread reading events, once, so no listener misses events read by
+    /// another one.
+    fn read_events(&self) {
+        self.reading.call_once(|| {
+            let listeners = Arc::clone(&self.listeners);
+            thread::spawn(move || loop {
+                let event = read().unwrap();
+                let listeners = listeners.lock().unwrap();
+                match event {
+                    Event::Key(event) => match event.code {
+                        KeyCode::Char(char) => {
+                            listeners.keys.iter().for_each(|tx| tx.send(char).unwrap())
+                        }
+                        KeyCode::Tab => listeners.keys.iter().for_each(|tx| tx.send('\t').unwrap()),
+                        KeyCode::Esc => listeners
+                            .keys
+                            .iter()
+                            .for_each(|tx| tx.send('\u{1b}').unwrap()),
+                        _ => {}
+                    },
+                    Event::Resize(width, height) => listeners
+                        .resizes
+                        .iter()
+                        .for_each(|tx| tx.send(TermSize { width, height }).unwrap()),
+                    Event::Mouse(MouseEvent {
+                        kind: MouseEventKind::Down(MouseButton::Left),
+                        column,
+                        row,
+                        ..
+                    }) => listeners
+                        .clicks
+                        .iter()
+                        .for_each(|tx| tx.send((column, row)).unwrap()),
+                    _ => {}
+                }
+            });
+        });
     }
 }
 
 impl Terminal for TermionTerminal {
-    fn write(&self, stdout: &mut dyn Write, row: u16, column: u16, content: &str) {
+    fn write_line(
+        &self,
+        stdout: &mut dyn Write,
+        row: u16,
+        column: u16,
+        line: &Line,
+        styler: &dyn Styler,
+    ) {
         stdout.queue(MoveTo(column, row)).unwrap();
-        stdout
-            .queue(style::PrintStyledContent(content.black()))
-            .unwrap();
+        for span in &line.spans {
+            let style = resolve_style(&span.style, styler);
+            let url = span
+                .style
+                .link
+                .as_deref()
+                .filter(|link| self.hyperlinks && is_external(link));
+            if let Some(url) = url {
+                stdout.queue(style::Print(hyperlink(url))).unwrap();
+            }
+            stdout
+                .queue(style::PrintStyledContent(style.apply(span.text.as_str())))
+                .unwrap();
+            if url.is_some() {
+                stdout.queue(style::Print(hyperlink(""))).unwrap();
+            }
+        }
     }
 
     fn clear(&self, stdout: &mut dyn Write) {
+        if let Some(Graphics::Kitty(_)) = self.graphics {
+            stdout.queue(style::Print(KITTY_CLEAR)).unwrap();
+        }
         stdout.queue(MoveTo(1, 1)).unwrap();
         stdout.execute(Clear(ClearType::All)).unwrap();
         stdout.execute(Clear(ClearType::Purge)).unwrap();
@@ -55,30 +168,98 @@ impl Terminal for TermionTerminal {
 
     fn on_input(&self) -> Result<Receiver<char>> {
         let (tx, rx) = mpsc::channel();
-
-        thread::spawn(move || loop {
-            match read().unwrap() {
-                Event::Key(event) => match event.code {
-                    KeyCode::Char(char) => tx.send(char).unwrap(),
-                    _ => {}
-                },
-                _ => {}
-            }
-        });
-
+        self.listeners.lock().unwrap().keys.push(tx);
+        self.read_events();
         Ok(rx)
     }
 
     fn on_resize(&self) -> Result<Receiver<TermSize>> {
         let (tx, rx) = mpsc::channel();
+        self.listeners.lock().unwrap().resizes.push(tx);
+        self.read_events();
+        Ok(rx)
+    }
 
-        thread::spawn(move || loop {
-            match read().unwrap() {
-                Event::Resize(width, height) => tx.send(TermSize { width, height }).unwrap(),
-                _ => {}
-            }
-        });
-
+    fn on_click(&self) -> Result<Receiver<(u16, u16)>> {
+        let (tx, rx) = mpsc::channel();
+        self.listeners.lock().unwrap().clicks.push(tx);
+        self.read_events();
         Ok(rx)
     }
+
+    fn graphics(&mut self) -> Graphics {
+        *self.graphics.get_or_insert_with(graphics::detect)
+    }
+
+    fn draw_image(
+        &self,
+        stdout: &mut dyn Write,
+        row: u16,
+        column: u16,
+        image: &RgbaImage,
+        columns: u16,
+        rows: u16,
+    ) {
+        let Some(cell) = self.graphics.and_then(|graphics| graphics.cell_size()) else {
+            return;
+        };
+        // Sent at the size it's shown, books often have huge images
+        let scaled = imageops::resize(
+            image,
+            (columns as u32 * cell.width).max(1),
+            (rows as u32 * cell.height).max(1),
+            FilterType::Triangle,
+        );
+        let sequence = match self.graphics {
+            Some(Graphics::Kitty(_)) => kitty(&scaled, row as u32 + 1, columns, rows),
+            _ => sixel(&scaled),
+        };
+        stdout.queue(MoveTo(column, row)).unwrap();
+        stdout.queue(style::Print(sequence)).unwrap();
+    }
+}
+
+/// OSC 8 sequence starting a hyperlink, or ending it when `url` is empty. Control
+/// characters are dropped so the URL can't end the sequence early.
+fn hyperlink(url: &str) -> String {
+    let url: String = url.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]8;;{}\x1b\\", url)
+}
+
+/// Layers styles of all keys, inner elements overriding what they set.
+fn resolve_style(style: &Style, styler: &dyn Styler) -> ContentStyle {
+    let mut resolved = ContentStyle::new();
+    for key in &style.keys {
+        let layer = styler.style(key);
+        if layer.foreground_color.is_some() {
+            resolved.foreground_color = layer.foreground_color;
+        }
+        if layer.background_color.is_some() {
+            resolved.background_color = layer.background_color;
+        }
+        if layer.underline_color.is_some() {
+            resolved.underline_color = layer.underline_color;
+        }
+        resolved.attributes.extend(layer.attributes);
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html::is_external;
+    use crate::term::hyperlink;
+
+    #[test]
+    fn external_links_become_hyperlinks() {
+        assert!(is_external("https://example.com/a#b"));
+        assert!(is_external("mailto:me@example.com"));
+        assert!(!is_external("chapter2.xhtml#note:1"));
+        assert!(!is_external("javascript:alert(1)"));
+        assert!(!is_external("file:///etc/passwd"));
+        assert_eq!(
+            hyperlink("https://a.b/\x1b]x"),
+            "\x1b]8;;https://a.b/]x\x1b\\"
+        );
+    }
 }
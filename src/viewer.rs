@@ -0,0 +1,100 @@
+use image::imageops;
+use image::RgbaImage;
+
+/// Zoom levels, each showing half the width and height of the previous one.
+const MAX_ZOOM: u32 = 4;
+
+/// Image shown over the whole screen, zoomed in on a part of it.
+pub struct ImageViewer {
+    pub path: String,
+    image: RgbaImage,
+    /// How many times the view was halved.
+    zoom: u32,
+    /// Middle of the view as a fraction of the image's width and height.
+    center: (f32, f32),
+}
+
+impl ImageViewer {
+    pub fn open(path: &str) -> Option<Self> {
+        Some(Self {
+            path: path.to_owned(),
+            image: image::open(path).ok()?.to_rgba8(),
+            zoom: 0,
+            center: (0.5, 0.5),
+        })
+    }
+
+    pub fn zoom(&self) -> u32 {
+        1 << self.zoom
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom + 1).min(MAX_ZOOM);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = self.zoom.saturating_sub(1);
+        self.clamp();
+    }
+
+    /// Moves the view by fractions of its own size.
+    pub fn pan(&mut self, x: f32, y: f32) {
+        let size = self.view_size();
+        self.center.0 += x * size;
+        self.center.1 += y * size;
+        self.clamp();
+    }
+
+    /// Part of the image in view.
+    pub fn view(&self) -> RgbaImage {
+        let size = self.view_size();
+        let (width, height) = self.image.dimensions();
+        let view_width = ((width as f32 * size).round() as u32).clamp(1, width.max(1));
+        let view_height = ((height as f32 * size).round() as u32).clamp(1, height.max(1));
+        let left = (self.center.0 - size / 2.0) * width as f32;
+        let top = (self.center.1 - size / 2.0) * height as f32;
+        let left = (left.round().max(0.0) as u32).min(width - view_width);
+        let top = (top.round().max(0.0) as u32).min(height - view_height);
+        imageops::crop_imm(&self.image, left, top, view_width, view_height).to_image()
+    }
+
+    /// Width and height of the view as a fraction of the image.
+    fn view_size(&self) -> f32 {
+        1.0 / self.zoom() as f32
+    }
+
+    /// Keeps the view inside the image.
+    fn clamp(&mut self) {
+        let half = self.view_size() / 2.0;
+        self.center.0 = self.center.0.clamp(half, 1.0 - half);
+        self.center.1 = self.center.1.clamp(half, 1.0 - half);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::viewer::ImageViewer;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn view_pans_and_zooms_inside_the_image() {
+        let mut image = RgbaImage::new(8, 4);
+        image.put_pixel(7, 3, Rgba([255, 0, 0, 255]));
+        let mut viewer = ImageViewer {
+            path: String::new(),
+            image,
+            zoom: 0,
+            center: (0.5, 0.5),
+        };
+        assert_eq!(viewer.view().dimensions(), (8, 4));
+
+        viewer.zoom_in();
+        viewer.pan(5.0, 5.0);
+        let view = viewer.view();
+        assert_eq!(view.dimensions(), (4, 2));
+        assert_eq!(view.get_pixel(3, 1), &Rgba([255, 0, 0, 255]));
+
+        viewer.zoom_out();
+        assert_eq!(viewer.view().dimensions(), (8, 4));
+    }
+}
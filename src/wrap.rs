@@ -0,0 +1,304 @@
+use crate::hyphen::{break_points, lang_from_tag, Hyphenation};
+use crate::line::{Line, Style};
+use std::ops::Range;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const SOFT_HYPHEN: char = '\u{AD}';
+/// Invisible break opportunity, dropped from wrapped lines.
+pub const ZERO_WIDTH_SPACE: &str = "\u{200B}";
+
+/// Number of terminal cells needed to draw the text. Soft hyphens are invisible
+/// unless a line is broken at them.
+pub fn width(text: &str) -> usize {
+    text.graphemes(true)
+        .filter(|grapheme| *grapheme != "\u{AD}")
+        .map(UnicodeWidthStr::width)
+        .sum()
+}
+
+/// Removes trailing spaces, non-breaking spaces are content and stay.
+fn trim_spaces(text: &str) -> &str {
+    text.trim_end_matches(|c: char| c.is_ascii_whitespace())
+}
+
+/// Width of a segment when the line ends after it, a trailing soft hyphen becomes visible.
+fn end_width(segment: &str) -> usize {
+    let visible = trim_spaces(segment);
+    width(visible) + usize::from(visible.ends_with(SOFT_HYPHEN))
+}
+
+/// Splits a line after as many graphemes as fit in `max_width` cells, but at least one
+/// so a grapheme wider than `max_width` still moves to the head.
+pub fn split_at_width(line: &Line, max_width: usize) -> (Line, Line) {
+    let mut head = Line::new();
+    let mut tail = Line::new();
+    let mut used = 0;
+    for span in &line.spans {
+        for grapheme in span.text.graphemes(true) {
+            let grapheme_width = width(grapheme);
+            if tail.spans.is_empty() && (used == 0 || used + grapheme_width <= max_width) {
+                head.push(grapheme, &span.style);
+                used += grapheme_width;
+            } else {
+                tail.push(grapheme, &span.style);
+            }
+        }
+    }
+    (head, tail)
+}
+
+/// Paragraph flattened to one string with the style of every byte range.
+struct Flat {
+    text: String,
+    styles: Vec<(Range<usize>, Style)>,
+}
+
+impl Flat {
+    fn new(paragraph: &Line) -> Self {
+        let mut text = String::new();
+        let mut styles = vec![];
+        for span in &paragraph.spans {
+            let start = text.len();
+            text.push_str(&span.text);
+            styles.push((start..text.len(), span.style.clone()));
+        }
+        Self { text, styles }
+    }
+
+    fn style_at(&self, index: usize) -> &Style {
+        self.styles
+            .iter()
+            .find(|(range, _)| range.contains(&index))
+            .map(|(_, style)| style)
+            .unwrap_or(&self.styles[0].1)
+    }
+
+    /// Whether `index` is inside the base of a ruby, which can't be broken.
+    fn inside_ruby(&self, index: usize) -> bool {
+        let ruby = &self.style_at(index).ruby;
+        index > 0 && ruby.is_some() && &self.style_at(index - 1).ruby == ruby
+    }
+
+    /// Line made of the text in `range`, shown as a hyphen if it was broken at a soft hyphen.
+    fn line(&self, range: Range<usize>) -> Line {
+        let trimmed = trim_spaces(&self.text[range.clone()]);
+        let end = range.start + trimmed.len();
+        let mut line = Line::new();
+        for (span_range, style) in &self.styles {
+            let start = span_range.start.max(range.start);
+            let stop = span_range.end.min(end);
+            if start < stop {
+                let text = self.text[start..stop]
+                    .replace(SOFT_HYPHEN, "")
+                    .replace(ZERO_WIDTH_SPACE, "");
+                line.push(&text, style);
+            }
+        }
+        if trimmed.ends_with(SOFT_HYPHEN) {
+            line.push("-", self.style_at(end - SOFT_HYPHEN.len_utf8()));
+        }
+        line
+    }
+}
+
+/// Breaks a paragraph into lines no wider than `max_width` cells, breaking at UAX #14
+/// opportunities, hyphenating long words when `hyphenation` allows it and splitting
+/// between grapheme clusters when a word alone is too wide.
+pub fn wrap(paragraph: &Line, max_width: usize, hyphenation: Option<&Hyphenation>) -> Vec<Line> {
+    wrap_indented(paragraph, max_width, 0, 0, hyphenation)
+}
+
+/// Like `wrap` but leaves `first_indent` cells free on the first line and `indent` cells
+/// on the following ones.
+pub fn wrap_indented(
+    paragraph: &Line,
+    max_width: usize,
+    first_indent: usize,
+    indent: usize,
+    hyphenation: Option<&Hyphenation>,
+) -> Vec<Line> {
+    let flat = Flat::new(paragraph);
+    let text = flat.text.as_str();
+    let mut limit = max_width.saturating_sub(first_indent).max(1);
+    let rest_width = max_width.saturating_sub(indent).max(1);
+
+    let mut lines: Vec<Line> = vec![];
+    let mut line_start = 0;
+    let mut line_width = 0;
+    let mut segment_start = 0;
+
+    for (segment_end, opportunity) in linebreaks(text) {
+        if opportunity == BreakOpportunity::Allowed && flat.inside_ruby(segment_end) {
+            continue;
+        }
+        loop {
+            let segment = &text[segment_start..segment_end];
+            let visible_width = end_width(segment);
+            if line_width + visible_width <= limit {
+                line_width += width(segment);
+                break;
+            }
+
+            let previous_hyphenated = lines.last().is_some_and(|line| line.text().ends_with('-'));
+            if let Some(split) = hyphenation.and_then(|hyphenation| {
+                hyphenate(
+                    &flat,
+                    segment_start,
+                    trim_spaces(segment),
+                    limit.saturating_sub(line_width),
+                    hyphenation,
+                    previous_hyphenated,
+                )
+            }) {
+                let mut line = flat.line(line_start..segment_start + split);
+                let style = flat.style_at(segment_start).clone();
+                line.push("-", &style);
+                lines.push(line);
+                limit = rest_width;
+                segment_start += split;
+                line_start = segment_start;
+                line_width = 0;
+                continue;
+            }
+
+            if line_width > 0 {
+                lines.push(flat.line(line_start..segment_start));
+                limit = rest_width;
+                line_start = segment_start;
+                line_width = 0;
+                continue;
+            }
+
+            // Nothing to break on, cut the word between graphemes
+            for (offset, grapheme) in segment.grapheme_indices(true) {
+                let grapheme_width = width(grapheme);
+                if line_width > 0 && line_width + grapheme_width > limit {
+                    lines.push(flat.line(line_start..segment_start + offset));
+                    limit = rest_width;
+                    line_start = segment_start + offset;
+                    line_width = 0;
+                }
+                line_width += grapheme_width;
+            }
+            break;
+        }
+        segment_start = segment_end;
+
+        if opportunity == BreakOpportunity::Mandatory && segment_end != text.len() {
+            let line_end = line_start + trim_spaces(&text[line_start..segment_end]).len();
+            lines.push(flat.line(line_start..line_end));
+            limit = rest_width;
+            line_start = segment_end;
+            line_width = 0;
+        }
+    }
+
+    if !trim_spaces(&text[line_start..]).is_empty() {
+        lines.push(flat.line(line_start..text.len()));
+    }
+    lines
+}
+
+/// Finds where to split `word` so its first part and a hyphen fill `space` cells.
+fn hyphenate(
+    flat: &Flat,
+    word_start: usize,
+    word: &str,
+    space: usize,
+    hyphenation: &Hyphenation,
+    previous_hyphenated: bool,
+) -> Option<usize> {
+    if !hyphenation.enabled || word.chars().count() < hyphenation.min_word_length {
+        return None;
+    }
+    let mut penalty = hyphenation.penalty;
+    if previous_hyphenated {
+        penalty += hyphenation.consecutive_penalty;
+    }
+    if space <= penalty {
+        return None;
+    }
+
+    let lang = lang_from_tag(flat.style_at(word_start).lang.as_deref()?)?;
+    break_points(word, lang)
+        .into_iter()
+        .rev()
+        .find(|point| width(&word[..*point]) < space)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hyphen::Hyphenation;
+    use crate::line::{Line, Style};
+    use crate::wrap::{width, wrap};
+
+    fn wrapped(text: &str, max_width: usize) -> Vec<String> {
+        wrap(&Line::from(text), max_width, None)
+            .iter()
+            .map(|line| line.text())
+            .collect()
+    }
+
+    #[test]
+    fn wraps_on_spaces_and_hyphens() {
+        assert_eq!(wrapped("ab cd ef", 5), vec!["ab cd", "ef"]);
+        assert_eq!(
+            wrapped("well-known fact", 6),
+            vec!["well-", "known", "fact"]
+        );
+    }
+
+    #[test]
+    fn wraps_cjk_between_characters() {
+        assert_eq!(wrapped("日本語の文章", 4), vec!["日本", "語の", "文章"]);
+        assert_eq!(width("日本"), 4);
+    }
+
+    #[test]
+    fn combining_marks_take_no_space() {
+        assert_eq!(width("e\u{301}te\u{301}"), 3);
+        assert_eq!(
+            wrapped("e\u{301}te\u{301} e\u{301}te\u{301}", 7),
+            vec!["e\u{301}te\u{301} e\u{301}te\u{301}"]
+        );
+    }
+
+    #[test]
+    fn cuts_words_wider_than_line() {
+        assert_eq!(wrapped("abcdefgh", 3), vec!["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn hyphenates_by_language() {
+        let hyphenation = Hyphenation::new();
+        let mut polish = Line::new();
+        let style = Style {
+            lang: Some("pl".to_owned()),
+            ..Style::default()
+        };
+        polish.push("ala przedszkole", &style);
+
+        let lines: Vec<String> = wrap(&polish, 12, Some(&hyphenation))
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(lines, vec!["ala przed-", "szkole"]);
+
+        let mut unknown = polish.clone();
+        unknown.spans[0].style.lang = None;
+        let lines: Vec<String> = wrap(&unknown, 12, Some(&hyphenation))
+            .iter()
+            .map(|line| line.text())
+            .collect();
+        assert_eq!(lines, vec!["ala", "przedszkole"]);
+    }
+
+    #[test]
+    fn respects_non_breaking_spaces_and_soft_hyphens() {
+        assert_eq!(wrapped("a b\u{a0}c", 4), vec!["a", "b\u{a0}c"]);
+        assert_eq!(wrapped("super\u{ad}cali", 7), vec!["super-", "cali"]);
+        assert_eq!(wrapped("super\u{ad}cali", 9), vec!["supercali"]);
+    }
+}